@@ -0,0 +1,791 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use super::disassembler::{InstructionSet, Syntax};
+use super::PROGRAM_ADDRESS;
+
+const KEYWORDS: [&str; 10] = ["[i]", "b", "dt", "f", "hf", "i", "k", "long", "r", "st"];
+const MNEMONICS: [&str; 32] = [
+    "add", "and", "audio", "call", "cls", "drw", "exit", "high", "jp", "ld", "load", "low", "or",
+    "pitch", "plane", "ret", "rnd", "save", "scd", "scl", "scr", "scu", "se", "shl", "shr", "sknp",
+    "skp", "sne", "sub", "subn", "sys", "xor",
+];
+/// First words of the statements of Octo's syntax, besides registers
+const OCTO_MNEMONICS: [&str; 25] = [
+    ":call",
+    "audio",
+    "bcd",
+    "buzzer",
+    "clear",
+    "delay",
+    "exit",
+    "hires",
+    "i",
+    "if",
+    "jump",
+    "jump0",
+    "load",
+    "loadflags",
+    "lores",
+    "pitch",
+    "plane",
+    "return",
+    "save",
+    "saveflags",
+    "scroll-down",
+    "scroll-left",
+    "scroll-right",
+    "scroll-up",
+    "sprite",
+];
+/// Operators of Octo's syntax that take two registers or a register and a byte, with their
+/// mnemonics
+const OCTO_OPERATORS: [(&str, &str); 9] = [
+    ("&=", "and"),
+    ("+=", "add"),
+    ("-=", "sub"),
+    (":=", "ld"),
+    ("<<=", "shl"),
+    ("=-", "subn"),
+    (">>=", "shr"),
+    ("^=", "xor"),
+    ("|=", "or"),
+];
+const SPRITE_ROW_WIDTH: usize = 8;
+
+#[derive(Debug)]
+pub struct AssemblerError {
+    pub column: usize,
+    pub kind: AssemblerErrorKind,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub enum AssemblerErrorKind {
+    DuplicateSymbol(String),
+    InvalidNumber(String),
+    InvalidOperands(String),
+    InvalidSprite,
+    InvalidSymbol(String),
+    UndefinedSymbol(String),
+    UnknownDirective(String),
+    UnknownMnemonic(String),
+    UnsupportedInstruction(String),
+    ValueOutOfRange(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Keyword(&'static str),
+    Register(u16),
+    Value(usize),
+}
+
+struct Statement<'a> {
+    line: usize,
+    mnemonic: Token<'a>,
+    operands: Vec<Token<'a>>,
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    column: usize,
+    text: &'a str,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}, {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl fmt::Display for AssemblerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateSymbol(name) => write!(f, "symbol '{}' is already defined", name),
+            Self::InvalidNumber(text) => write!(f, "invalid number '{}'", text),
+            Self::InvalidOperands(mnemonic) => write!(f, "invalid operands for '{}'", mnemonic),
+            Self::InvalidSprite => write!(f, "sprite rows must be made of '#' and '.'"),
+            Self::InvalidSymbol(name) => write!(f, "'{}' can't be used as a symbol name", name),
+            Self::UndefinedSymbol(name) => write!(f, "undefined symbol '{}'", name),
+            Self::UnknownDirective(directive) => write!(f, "unknown directive '{}'", directive),
+            Self::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic '{}'", mnemonic),
+            Self::UnsupportedInstruction(mnemonic) => write!(
+                f,
+                "'{}' isn't supported by the selected instruction set",
+                mnemonic
+            ),
+            Self::ValueOutOfRange(value) => write!(f, "value {} is out of range", value),
+        }
+    }
+}
+
+impl error::Error for AssemblerError {}
+
+/// Assemble a program to be loaded at [`PROGRAM_ADDRESS`]
+///
+/// Both syntaxes the disassembler writes are understood, one statement per line. With
+/// [`Syntax::Cowgod`]:
+///
+/// ```text
+/// ; comments start with a semicolon
+/// :const SPEED 2          ; constants, the value can refer to symbols defined above
+/// loop:                   ; labels, a statement can follow on the same line
+///     ld v0, SPEED        ; instructions, operands are separated by commas
+///     ld i, ball
+///     drw v1, v2, 4
+///     jp loop
+/// ball:
+///     :sprite .####. ###### ###### .####.     ; sprite rows, '#' is lit and '.' isn't
+///     :byte 0x12 0b0101 255                   ; raw bytes
+/// ```
+///
+/// Mnemonics, registers and keywords (`i`, `dt`, `k`, ...) are case insensitive. Numbers are
+/// decimal, hexadecimal (`0x`) or binary (`0b`). Sprite rows are padded to a multiple of 8 pixels
+/// with unlit pixels, 16 pixels wide rows take up two bytes
+///
+/// With [`Syntax::Octo`], comments start with `#` and labels are written `: name`, the
+/// directives are the same but raw bytes can also be written without `:byte`. Only the
+/// statements the disassembler writes are understood, Octo's macros and control structures
+/// aren't
+pub fn assemble(
+    source: &str,
+    instruction_set: InstructionSet,
+    syntax: Syntax,
+) -> Result<Vec<u8>, AssemblerError> {
+    let mut address = PROGRAM_ADDRESS;
+    let mut statements = Vec::new();
+    let mut symbols = HashMap::new();
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut tokens = tokenize(text, syntax);
+
+        match (syntax, tokens.as_slice()) {
+            (Syntax::Cowgod, [token, ..]) if !token.text.starts_with(':') => {
+                if let Some(name) = token.text.strip_suffix(':') {
+                    define(
+                        &mut symbols,
+                        Token {
+                            text: name,
+                            ..*token
+                        },
+                        address,
+                        line,
+                    )?;
+                    tokens.remove(0);
+                }
+            }
+
+            (Syntax::Octo, [token, rest @ ..]) if token.text == ":" => {
+                let Some(&name) = rest.first() else {
+                    return Err(invalid_operands(*token, line));
+                };
+
+                define(&mut symbols, name, address, line)?;
+                tokens.drain(..2);
+            }
+
+            _ => (),
+        }
+
+        if tokens.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = match syntax {
+            Syntax::Cowgod => (tokens[0], tokens[1..].to_vec()),
+            Syntax::Octo => octo(&tokens, line)?,
+        };
+
+        match mnemonic.text {
+            ":byte" => address += operands.len(),
+
+            ":const" => {
+                let [name, value] = operands[..] else {
+                    return Err(invalid_operands(mnemonic, line));
+                };
+
+                let value = evaluate(value, &symbols, line)?;
+                define(&mut symbols, name, value, line)?;
+                continue;
+            }
+
+            ":sprite" => address += sprite(mnemonic, &operands, line)?.len(),
+
+            directive if directive.starts_with(':') => {
+                return Err(AssemblerError {
+                    column: mnemonic.column,
+                    kind: AssemblerErrorKind::UnknownDirective(directive.to_string()),
+                    line,
+                })
+            }
+
+            _ => address += size(&operands),
+        }
+
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    let mut program = Vec::with_capacity(address - PROGRAM_ADDRESS);
+
+    for statement in statements {
+        match statement.mnemonic.text {
+            ":byte" => {
+                for operand in statement.operands {
+                    let value = evaluate(operand, &symbols, statement.line)?;
+                    program.push(fit(value, u8::MAX as usize, operand, statement.line)? as u8);
+                }
+            }
+
+            ":sprite" => program.extend(sprite(
+                statement.mnemonic,
+                &statement.operands,
+                statement.line,
+            )?),
+
+            _ => program.extend(encode(&statement, &symbols, instruction_set)?),
+        }
+    }
+
+    Ok(program)
+}
+
+fn define<'a>(
+    symbols: &mut HashMap<&'a str, usize>,
+    name: Token<'a>,
+    value: usize,
+    line: usize,
+) -> Result<(), AssemblerError> {
+    let valid = name
+        .text
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && register(name.text).is_none()
+        && keyword(name.text).is_none();
+
+    if !valid {
+        return Err(AssemblerError {
+            column: name.column,
+            kind: AssemblerErrorKind::InvalidSymbol(name.text.to_string()),
+            line,
+        });
+    }
+
+    if symbols.insert(name.text, value).is_some() {
+        return Err(AssemblerError {
+            column: name.column,
+            kind: AssemblerErrorKind::DuplicateSymbol(name.text.to_string()),
+            line,
+        });
+    }
+
+    Ok(())
+}
+
+fn encode(
+    statement: &Statement,
+    symbols: &HashMap<&str, usize>,
+    instruction_set: InstructionSet,
+) -> Result<Vec<u8>, AssemblerError> {
+    use InstructionSet::{Chip8, SuperChip, XoChip};
+    use Operand::{Keyword, Register, Value};
+
+    const ADDRESS: usize = 0xFFF;
+    const BYTE: usize = 0xFF;
+    const NIBBLE: usize = 0xF;
+
+    let line = statement.line;
+    let mnemonic = statement.mnemonic.text.to_ascii_lowercase();
+
+    let operands = statement
+        .operands
+        .iter()
+        .map(|&token| operand(token, symbols, line))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the index of the operand is needed to point at it if it doesn't fit
+    let fits =
+        |index: usize, value: usize, max: usize| fit(value, max, statement.operands[index], line);
+
+    let (required, word) = match (mnemonic.as_str(), operands.as_slice()) {
+        ("add", [Register(x), Value(nn)]) => (Chip8, 0x7000 | x << 8 | fits(1, *nn, BYTE)?),
+        ("add", [Register(x), Register(y)]) => (Chip8, 0x8004 | x << 8 | y << 4),
+        ("add", [Keyword("i"), Register(x)]) => (Chip8, 0xF01E | x << 8),
+        ("and", [Register(x), Register(y)]) => (Chip8, 0x8002 | x << 8 | y << 4),
+        ("audio", []) => (XoChip, 0xF002),
+        ("call", [Value(nnn)]) => (Chip8, 0x2000 | fits(0, *nnn, ADDRESS)?),
+        ("cls", []) => (Chip8, 0x00E0),
+        ("drw", [Register(x), Register(y), Value(n)]) => {
+            (Chip8, 0xD000 | x << 8 | y << 4 | fits(2, *n, NIBBLE)?)
+        }
+        ("exit", []) => (SuperChip, 0x00FD),
+        ("high", []) => (SuperChip, 0x00FF),
+        ("jp", [Value(nnn)]) => (Chip8, 0x1000 | fits(0, *nnn, ADDRESS)?),
+        ("jp", [Register(0), Value(nnn)]) => (Chip8, 0xB000 | fits(1, *nnn, ADDRESS)?),
+        ("ld", [Register(x), Value(nn)]) => (Chip8, 0x6000 | x << 8 | fits(1, *nn, BYTE)?),
+        ("ld", [Register(x), Register(y)]) => (Chip8, 0x8000 | x << 8 | y << 4),
+        ("ld", [Register(x), Keyword("[i]")]) => (Chip8, 0xF065 | x << 8),
+        ("ld", [Register(x), Keyword("dt")]) => (Chip8, 0xF007 | x << 8),
+        ("ld", [Register(x), Keyword("k")]) => (Chip8, 0xF00A | x << 8),
+        ("ld", [Register(x), Keyword("r")]) => (SuperChip, 0xF085 | x << 8),
+        ("ld", [Keyword("[i]"), Register(x)]) => (Chip8, 0xF055 | x << 8),
+        ("ld", [Keyword("b"), Register(x)]) => (Chip8, 0xF033 | x << 8),
+        ("ld", [Keyword("dt"), Register(x)]) => (Chip8, 0xF015 | x << 8),
+        ("ld", [Keyword("f"), Register(x)]) => (Chip8, 0xF029 | x << 8),
+        ("ld", [Keyword("hf"), Register(x)]) => (SuperChip, 0xF030 | x << 8),
+        ("ld", [Keyword("i"), Value(nnn)]) => (Chip8, 0xA000 | fits(1, *nnn, ADDRESS)?),
+        ("ld", [Keyword("i"), Keyword("long"), Value(nnnn)]) => {
+            if instruction_set < XoChip {
+                return Err(unsupported(statement, mnemonic));
+            }
+
+            let mut bytes = vec![0xF0, 0x00];
+            bytes.extend(fits(2, *nnnn, u16::MAX as usize)?.to_be_bytes());
+            return Ok(bytes);
+        }
+        ("ld", [Keyword("r"), Register(x)]) => (SuperChip, 0xF075 | x << 8),
+        ("ld", [Keyword("st"), Register(x)]) => (Chip8, 0xF018 | x << 8),
+        ("load", [Register(x), Register(y)]) => (XoChip, 0x5003 | x << 8 | y << 4),
+        ("low", []) => (SuperChip, 0x00FE),
+        ("or", [Register(x), Register(y)]) => (Chip8, 0x8001 | x << 8 | y << 4),
+        ("pitch", [Register(x)]) => (XoChip, 0xF03A | x << 8),
+        ("plane", [Value(n)]) => (XoChip, 0xF001 | fits(0, *n, NIBBLE)? << 8),
+        ("ret", []) => (Chip8, 0x00EE),
+        ("rnd", [Register(x), Value(nn)]) => (Chip8, 0xC000 | x << 8 | fits(1, *nn, BYTE)?),
+        ("save", [Register(x), Register(y)]) => (XoChip, 0x5002 | x << 8 | y << 4),
+        ("scd", [Value(n)]) => (SuperChip, 0x00C0 | fits(0, *n, NIBBLE)?),
+        ("scl", []) => (SuperChip, 0x00FC),
+        ("scr", []) => (SuperChip, 0x00FB),
+        ("scu", [Value(n)]) => (XoChip, 0x00D0 | fits(0, *n, NIBBLE)?),
+        ("se", [Register(x), Value(nn)]) => (Chip8, 0x3000 | x << 8 | fits(1, *nn, BYTE)?),
+        ("se", [Register(x), Register(y)]) => (Chip8, 0x5000 | x << 8 | y << 4),
+        ("shl", [Register(x)]) => (Chip8, 0x800E | x << 8 | x << 4),
+        ("shl", [Register(x), Register(y)]) => (Chip8, 0x800E | x << 8 | y << 4),
+        ("shr", [Register(x)]) => (Chip8, 0x8006 | x << 8 | x << 4),
+        ("shr", [Register(x), Register(y)]) => (Chip8, 0x8006 | x << 8 | y << 4),
+        ("sknp", [Register(x)]) => (Chip8, 0xE0A1 | x << 8),
+        ("skp", [Register(x)]) => (Chip8, 0xE09E | x << 8),
+        ("sne", [Register(x), Value(nn)]) => (Chip8, 0x4000 | x << 8 | fits(1, *nn, BYTE)?),
+        ("sne", [Register(x), Register(y)]) => (Chip8, 0x9000 | x << 8 | y << 4),
+        ("sub", [Register(x), Register(y)]) => (Chip8, 0x8005 | x << 8 | y << 4),
+        ("subn", [Register(x), Register(y)]) => (Chip8, 0x8007 | x << 8 | y << 4),
+        ("sys", [Value(nnn)]) => (Chip8, fits(0, *nnn, ADDRESS)?),
+        ("xor", [Register(x), Register(y)]) => (Chip8, 0x8003 | x << 8 | y << 4),
+
+        (mnemonic, _) if MNEMONICS.contains(&mnemonic) => {
+            return Err(invalid_operands(statement.mnemonic, line))
+        }
+
+        _ => {
+            return Err(AssemblerError {
+                column: statement.mnemonic.column,
+                kind: AssemblerErrorKind::UnknownMnemonic(statement.mnemonic.text.to_string()),
+                line,
+            })
+        }
+    };
+
+    if required > instruction_set {
+        return Err(unsupported(statement, mnemonic));
+    }
+
+    Ok(word.to_be_bytes().to_vec())
+}
+
+fn evaluate(
+    token: Token,
+    symbols: &HashMap<&str, usize>,
+    line: usize,
+) -> Result<usize, AssemblerError> {
+    let text = token.text;
+
+    let value = if let Some(digits) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        usize::from_str_radix(digits, 16)
+    } else if let Some(digits) = text.strip_prefix("0b").or(text.strip_prefix("0B")) {
+        usize::from_str_radix(digits, 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse()
+    } else {
+        return symbols.get(text).copied().ok_or_else(|| AssemblerError {
+            column: token.column,
+            kind: AssemblerErrorKind::UndefinedSymbol(text.to_string()),
+            line,
+        });
+    };
+
+    value.map_err(|_| AssemblerError {
+        column: token.column,
+        kind: AssemblerErrorKind::InvalidNumber(text.to_string()),
+        line,
+    })
+}
+
+fn fit(value: usize, max: usize, token: Token, line: usize) -> Result<u16, AssemblerError> {
+    match value <= max {
+        true => Ok(value as u16),
+        false => Err(AssemblerError {
+            column: token.column,
+            kind: AssemblerErrorKind::ValueOutOfRange(value),
+            line,
+        }),
+    }
+}
+
+fn invalid_operands(mnemonic: Token, line: usize) -> AssemblerError {
+    AssemblerError {
+        column: mnemonic.column,
+        kind: AssemblerErrorKind::InvalidOperands(mnemonic.text.to_string()),
+        line,
+    }
+}
+
+fn keyword(text: &str) -> Option<&'static str> {
+    KEYWORDS
+        .into_iter()
+        .find(|keyword| keyword.eq_ignore_ascii_case(text))
+}
+
+/// Translate a statement written in Octo's syntax into the mnemonic and operands of the same
+/// statement written in Cowgod's syntax
+fn octo<'a>(
+    tokens: &[Token<'a>],
+    line: usize,
+) -> Result<(Token<'a>, Vec<Token<'a>>), AssemblerError> {
+    let first = tokens[0];
+    let texts: Vec<&str> = tokens.iter().map(|token| token.text).collect();
+    let t = tokens;
+
+    // operands that are implied by Octo's syntax point at the start of the statement
+    let implied = |text: &'static str| Token {
+        column: first.column,
+        text,
+    };
+
+    let (mnemonic, operands) = match texts.as_slice() {
+        [":call", _] => ("call", vec![t[1]]),
+        [directive, ..] if directive.starts_with(':') && *directive != ":call" => {
+            return Ok((first, t[1..].to_vec()))
+        }
+        _ if texts
+            .iter()
+            .all(|text| text.starts_with(|c: char| c.is_ascii_digit())) =>
+        {
+            (":byte", t.to_vec())
+        }
+
+        ["audio"] => ("audio", vec![]),
+        ["bcd", _] => ("ld", vec![implied("b"), t[1]]),
+        ["buzzer", ":=", _] => ("ld", vec![implied("st"), t[2]]),
+        ["clear"] => ("cls", vec![]),
+        ["delay", ":=", _] => ("ld", vec![implied("dt"), t[2]]),
+        ["exit"] => ("exit", vec![]),
+        ["hires"] => ("high", vec![]),
+        ["i", ":=", "bighex", _] => ("ld", vec![implied("hf"), t[3]]),
+        ["i", ":=", "hex", _] => ("ld", vec![implied("f"), t[3]]),
+        ["i", ":=", "long", _] => ("ld", vec![t[0], t[2], t[3]]),
+        ["i", ":=", _] => ("ld", vec![t[0], t[2]]),
+        ["i", "+=", _] => ("add", vec![t[0], t[2]]),
+        ["if", _, "!=", _, "then"] => ("se", vec![t[1], t[3]]),
+        ["if", _, "==", _, "then"] => ("sne", vec![t[1], t[3]]),
+        ["if", _, "-key", "then"] => ("skp", vec![t[1]]),
+        ["if", _, "key", "then"] => ("sknp", vec![t[1]]),
+        ["jump", _] => ("jp", vec![t[1]]),
+        ["jump0", _] => ("jp", vec![implied("v0"), t[1]]),
+        ["load", _] => ("ld", vec![t[1], implied("[i]")]),
+        ["load", _, "-", _] => ("load", vec![t[1], t[3]]),
+        ["loadflags", _] => ("ld", vec![t[1], implied("r")]),
+        ["lores"] => ("low", vec![]),
+        ["pitch", ":=", _] => ("pitch", vec![t[2]]),
+        ["plane", _] => ("plane", vec![t[1]]),
+        ["return"] => ("ret", vec![]),
+        ["save", _] => ("ld", vec![implied("[i]"), t[1]]),
+        ["save", _, "-", _] => ("save", vec![t[1], t[3]]),
+        ["saveflags", _] => ("ld", vec![implied("r"), t[1]]),
+        ["scroll-down", _] => ("scd", vec![t[1]]),
+        ["scroll-left"] => ("scl", vec![]),
+        ["scroll-right"] => ("scr", vec![]),
+        ["scroll-up", _] => ("scu", vec![t[1]]),
+        ["sprite", _, _, _] => ("drw", vec![t[1], t[2], t[3]]),
+
+        [name, ..] if register(name).is_some() => match texts[1..] {
+            [":=", "delay"] => ("ld", vec![t[0], implied("dt")]),
+            [":=", "key"] => ("ld", vec![t[0], implied("k")]),
+            [":=", "random", _] => ("rnd", vec![t[0], t[3]]),
+            [operator, _] => match OCTO_OPERATORS.iter().find(|(text, _)| *text == operator) {
+                Some((_, mnemonic)) => (*mnemonic, vec![t[0], t[2]]),
+                None => return Err(invalid_operands(first, line)),
+            },
+            _ => return Err(invalid_operands(first, line)),
+        },
+
+        [mnemonic, ..] if OCTO_MNEMONICS.contains(mnemonic) => {
+            return Err(invalid_operands(first, line))
+        }
+
+        // calls are written as the bare name of the subroutine
+        [_] => ("call", vec![t[0]]),
+
+        _ => {
+            return Err(AssemblerError {
+                column: first.column,
+                kind: AssemblerErrorKind::UnknownMnemonic(first.text.to_string()),
+                line,
+            })
+        }
+    };
+
+    Ok((implied(mnemonic), operands))
+}
+
+fn operand(
+    token: Token,
+    symbols: &HashMap<&str, usize>,
+    line: usize,
+) -> Result<Operand, AssemblerError> {
+    if let Some(register) = register(token.text) {
+        return Ok(Operand::Register(register));
+    }
+
+    if let Some(keyword) = keyword(token.text) {
+        return Ok(Operand::Keyword(keyword));
+    }
+
+    evaluate(token, symbols, line).map(Operand::Value)
+}
+
+fn register(text: &str) -> Option<u16> {
+    match text.as_bytes() {
+        [b'v' | b'V', digit] => (*digit as char).to_digit(16).map(|digit| digit as u16),
+        _ => None,
+    }
+}
+
+/// Number of bytes an instruction takes up
+fn size(operands: &[Token]) -> usize {
+    match operands.get(1) {
+        Some(operand) if operand.text.eq_ignore_ascii_case("long") => 4,
+        _ => 2,
+    }
+}
+
+fn sprite(directive: Token, rows: &[Token], line: usize) -> Result<Vec<u8>, AssemblerError> {
+    if rows.is_empty() {
+        return Err(invalid_operands(directive, line));
+    }
+
+    let mut bytes = Vec::new();
+
+    for row in rows {
+        for (chunk_index, chunk) in row.text.as_bytes().chunks(SPRITE_ROW_WIDTH).enumerate() {
+            let mut byte = 0;
+
+            for (i, pixel) in chunk.iter().enumerate() {
+                match pixel {
+                    b'#' => byte |= 0x80 >> i,
+                    b'.' => (),
+                    _ => {
+                        return Err(AssemblerError {
+                            column: row.column + chunk_index * SPRITE_ROW_WIDTH + i,
+                            kind: AssemblerErrorKind::InvalidSprite,
+                            line,
+                        })
+                    }
+                }
+            }
+
+            bytes.push(byte);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Split a line into tokens, dropping the comment
+///
+/// Octo's comments start with `#`, which sprite rows are made of, so they can't follow a sprite
+fn tokenize(line: &str, syntax: Syntax) -> Vec<Token<'_>> {
+    let code = match syntax {
+        Syntax::Cowgod => line.split(';').next(),
+        Syntax::Octo if line.trim_start().starts_with(":sprite") => Some(line),
+        Syntax::Octo => line.split('#').next(),
+    }
+    .unwrap_or_default();
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (i, c) in code.char_indices().chain([(code.len(), ' ')]) {
+        match (c.is_whitespace() || c == ',', start) {
+            (true, Some(begin)) => {
+                tokens.push(Token {
+                    column: begin + 1,
+                    text: &code[begin..i],
+                });
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => (),
+        }
+    }
+
+    tokens
+}
+
+fn unsupported(statement: &Statement, mnemonic: String) -> AssemblerError {
+    AssemblerError {
+        column: statement.mnemonic.column,
+        kind: AssemblerErrorKind::UnsupportedInstruction(mnemonic),
+        line: statement.line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::disassembler::disassemble;
+    use super::*;
+
+    const INSTRUCTION_SETS: [InstructionSet; 3] = [
+        InstructionSet::Chip8,
+        InstructionSet::SuperChip,
+        InstructionSet::XoChip,
+    ];
+    const SYNTAXES: [Syntax; 2] = [Syntax::Cowgod, Syntax::Octo];
+
+    fn fail(source: &str, syntax: Syntax) -> AssemblerError {
+        assemble(source, InstructionSet::XoChip, syntax).unwrap_err()
+    }
+
+    #[test]
+    fn error_positions() {
+        for syntax in SYNTAXES {
+            let error = fail("cls\n:sprite ########x", syntax);
+            assert!(matches!(error.kind, AssemblerErrorKind::InvalidSprite));
+            assert_eq!((error.line, error.column), (2, 17));
+
+            let error = fail(":sprite ## #.x", syntax);
+            assert!(matches!(error.kind, AssemblerErrorKind::InvalidSprite));
+            assert_eq!((error.line, error.column), (1, 14));
+        }
+
+        let error = fail("    foo v0", Syntax::Cowgod);
+        assert!(matches!(error.kind, AssemblerErrorKind::UnknownMnemonic(_)));
+        assert_eq!((error.line, error.column), (1, 5));
+
+        let error = fail("    foo v0 v1", Syntax::Octo);
+        assert!(matches!(error.kind, AssemblerErrorKind::UnknownMnemonic(_)));
+        assert_eq!((error.line, error.column), (1, 5));
+
+        let error = fail("ld v0, 0x100", Syntax::Cowgod);
+        assert!(matches!(
+            error.kind,
+            AssemblerErrorKind::ValueOutOfRange(0x100)
+        ));
+        assert_eq!((error.line, error.column), (1, 8));
+
+        let error = fail("v0 := 0x100", Syntax::Octo);
+        assert!(matches!(
+            error.kind,
+            AssemblerErrorKind::ValueOutOfRange(0x100)
+        ));
+        assert_eq!((error.line, error.column), (1, 7));
+
+        let error = fail("cls ; clear\n  jp nowhere", Syntax::Cowgod);
+        assert!(matches!(error.kind, AssemblerErrorKind::UndefinedSymbol(_)));
+        assert_eq!((error.line, error.column), (2, 6));
+
+        let error = fail("clear # cls\n  jump nowhere", Syntax::Octo);
+        assert!(matches!(error.kind, AssemblerErrorKind::UndefinedSymbol(_)));
+        assert_eq!((error.line, error.column), (2, 8));
+
+        let error = fail("save v0 v1", Syntax::Octo);
+        assert!(matches!(error.kind, AssemblerErrorKind::InvalidOperands(_)));
+        assert_eq!((error.line, error.column), (1, 1));
+
+        let error = assemble("  high", InstructionSet::Chip8, Syntax::Cowgod).unwrap_err();
+        assert!(matches!(
+            error.kind,
+            AssemblerErrorKind::UnsupportedInstruction(_)
+        ));
+        assert_eq!((error.line, error.column), (1, 3));
+    }
+
+    #[test]
+    fn labels_and_directives() {
+        let cowgod = "\
+            :const SPEED 2
+            start: ld v0, SPEED ; speed
+            jp start
+            :sprite .####. ##############..
+            :byte 0x12 0b0101 255";
+        let octo = "\
+            :const SPEED 2
+            : start v0 := SPEED # speed
+            jump start
+            :sprite .####. ##############..
+            :byte 0x12 0b0101
+            255";
+        let expected = [0x60, 0x02, 0x12, 0x00, 0x78, 0xFF, 0xFC, 0x12, 0x05, 0xFF];
+
+        assert_eq!(
+            assemble(cowgod, InstructionSet::Chip8, Syntax::Cowgod).unwrap(),
+            expected
+        );
+        assert_eq!(
+            assemble(octo, InstructionSet::Chip8, Syntax::Octo).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        // every instruction word, the odd byte at the end can only be written out as data
+        let mut program: Vec<u8> = (0..=u16::MAX).flat_map(u16::to_be_bytes).collect();
+        program.push(0xF0);
+
+        for instruction_set in INSTRUCTION_SETS {
+            for syntax in SYNTAXES {
+                let listing = disassemble(&program, PROGRAM_ADDRESS, instruction_set, syntax);
+                let assembled =
+                    assemble(&listing, instruction_set, syntax).unwrap_or_else(|error| {
+                        panic!("{:?} {:?}: {}", instruction_set, syntax, error)
+                    });
+
+                assert!(
+                    assembled == program,
+                    "{:?} {:?} didn't round trip",
+                    instruction_set,
+                    syntax
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Machine cycles of the long branches and skips, the other instructions take 2
+const LONG_CYCLES: u32 = 3;
+const SHORT_CYCLES: u32 = 2;
+
+/// An RCA CDP1802, the CPU of the COSMAC VIP
+///
+/// There are no interrupts, DMA or I/O devices, output goes nowhere and input reads as 0. Addresses
+/// wrap around the end of memory, which has to be a power of two in size
+pub struct Cpu {
+    pub d: u8,
+    pub df: bool,
+    pub ie: bool,
+    /// Selects the program counter
+    pub p: u8,
+    pub q: bool,
+    pub registers: [u16; 16],
+    pub t: u8,
+    /// Selects the data pointer
+    pub x: u8,
+}
+
+impl Cpu {
+    /// Add `a`, `b` and the carry into D, setting DF on overflow, subtraction is done by adding the
+    /// complement so DF is set when there's no borrow
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+
+        self.d = sum as u8;
+        self.df = sum > u8::MAX as u16;
+    }
+
+    /// Fetch the next byte at the program counter
+    fn immediate(&mut self, memory: &[u8]) -> u8 {
+        let byte = read(memory, self.registers[self.p as usize]);
+        self.registers[self.p as usize] = self.registers[self.p as usize].wrapping_add(1);
+
+        byte
+    }
+
+    /// Take a long branch if `condition` holds, otherwise skip over the address
+    fn long_branch(&mut self, memory: &[u8], condition: bool) {
+        let pc = self.registers[self.p as usize];
+
+        self.registers[self.p as usize] = if condition {
+            u16::from_be_bytes([read(memory, pc), read(memory, pc.wrapping_add(1))])
+        } else {
+            pc.wrapping_add(2)
+        };
+    }
+
+    /// Skip the next 2 bytes if `condition` holds
+    fn long_skip(&mut self, condition: bool) {
+        if condition {
+            self.registers[self.p as usize] = self.registers[self.p as usize].wrapping_add(2);
+        }
+    }
+
+    pub fn new() -> Self {
+        Self {
+            d: 0,
+            df: false,
+            ie: true,
+            p: 0,
+            q: false,
+            registers: [0; 16],
+            t: 0,
+            x: 0,
+        }
+    }
+
+    /// Take a short branch within the current page if `condition` holds, otherwise skip over the
+    /// address
+    fn short_branch(&mut self, memory: &[u8], condition: bool) {
+        let pc = self.registers[self.p as usize];
+
+        self.registers[self.p as usize] = if condition {
+            pc & 0xFF00 | read(memory, pc) as u16
+        } else {
+            pc.wrapping_add(1)
+        };
+    }
+
+    /// Execute a single instruction, returns the number of machine cycles it took
+    pub fn step(&mut self, memory: &mut [u8]) -> u32 {
+        let opcode = self.immediate(memory);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            // IDL waits for DMA or an interrupt, neither of which is emulated
+            0x0 if n == 0 => {}
+            0x0 => self.d = read(memory, self.registers[n]),
+            0x1 => self.registers[n] = self.registers[n].wrapping_add(1),
+            0x2 => self.registers[n] = self.registers[n].wrapping_sub(1),
+
+            0x3 => {
+                // the EF flags are never set
+                let condition = match n {
+                    0x0 => true,
+                    0x1 => self.q,
+                    0x2 => self.d == 0,
+                    0x3 => self.df,
+                    0x4..=0x7 => false,
+                    0x9 => !self.q,
+                    0xA => self.d != 0,
+                    0xB => !self.df,
+                    0xC..=0xF => true,
+                    // SKP
+                    _ => {
+                        self.immediate(memory);
+                        return SHORT_CYCLES;
+                    }
+                };
+
+                self.short_branch(memory, condition);
+            }
+
+            0x4 => {
+                self.d = read(memory, self.registers[n]);
+                self.registers[n] = self.registers[n].wrapping_add(1);
+            }
+
+            0x5 => write(memory, self.registers[n], self.d),
+
+            0x6 => match n {
+                // IRX and OUT, which puts the byte on the bus
+                0x0..=0x7 => self.registers[x] = self.registers[x].wrapping_add(1),
+                0x8 => {}
+                _ => {
+                    write(memory, self.registers[x], 0);
+                    self.d = 0;
+                }
+            },
+
+            0x7 => match n {
+                0x0 | 0x1 => {
+                    let byte = read(memory, self.registers[x]);
+                    self.registers[x] = self.registers[x].wrapping_add(1);
+
+                    self.x = byte >> 4;
+                    self.p = byte & 0xF;
+                    self.ie = n == 0x0;
+                }
+
+                0x2 => {
+                    self.d = read(memory, self.registers[x]);
+                    self.registers[x] = self.registers[x].wrapping_add(1);
+                }
+
+                0x3 => {
+                    write(memory, self.registers[x], self.d);
+                    self.registers[x] = self.registers[x].wrapping_sub(1);
+                }
+
+                0x4 => self.add(read(memory, self.registers[x]), self.d, self.df),
+                0x5 => self.add(read(memory, self.registers[x]), !self.d, self.df),
+                0x6 => {
+                    let carry = self.d & 1 != 0;
+                    self.d = self.d >> 1 | (self.df as u8) << 7;
+                    self.df = carry;
+                }
+                0x7 => self.add(self.d, !read(memory, self.registers[x]), self.df),
+                0x8 => write(memory, self.registers[x], self.t),
+
+                0x9 => {
+                    self.t = self.x << 4 | self.p;
+                    write(memory, self.registers[2], self.t);
+                    self.x = self.p;
+                    self.registers[2] = self.registers[2].wrapping_sub(1);
+                }
+
+                0xA => self.q = false,
+                0xB => self.q = true,
+
+                0xC => {
+                    let byte = self.immediate(memory);
+                    self.add(byte, self.d, self.df);
+                }
+                0xD => {
+                    let byte = self.immediate(memory);
+                    self.add(byte, !self.d, self.df);
+                }
+                0xE => {
+                    let carry = self.d & 0x80 != 0;
+                    self.d = self.d << 1 | self.df as u8;
+                    self.df = carry;
+                }
+                _ => {
+                    let byte = self.immediate(memory);
+                    self.add(self.d, !byte, self.df);
+                }
+            },
+
+            0x8 => self.d = self.registers[n] as u8,
+            0x9 => self.d = (self.registers[n] >> 8) as u8,
+            0xA => self.registers[n] = self.registers[n] & 0xFF00 | self.d as u16,
+            0xB => self.registers[n] = self.registers[n] & 0x00FF | (self.d as u16) << 8,
+
+            0xC => {
+                match n {
+                    0x0 => self.long_branch(memory, true),
+                    0x1 => self.long_branch(memory, self.q),
+                    0x2 => self.long_branch(memory, self.d == 0),
+                    0x3 => self.long_branch(memory, self.df),
+                    0x4 => {}
+                    0x5 => self.long_skip(!self.q),
+                    0x6 => self.long_skip(self.d != 0),
+                    0x7 => self.long_skip(!self.df),
+                    0x8 => self.long_skip(true),
+                    0x9 => self.long_branch(memory, !self.q),
+                    0xA => self.long_branch(memory, self.d != 0),
+                    0xB => self.long_branch(memory, !self.df),
+                    0xC => self.long_skip(self.ie),
+                    0xD => self.long_skip(self.q),
+                    0xE => self.long_skip(self.d == 0),
+                    _ => self.long_skip(self.df),
+                }
+
+                return LONG_CYCLES;
+            }
+
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+
+            _ => {
+                let operand = match n {
+                    0x0..=0x5 | 0x7 => read(memory, self.registers[x]),
+                    0x6 | 0xE => 0,
+                    _ => self.immediate(memory),
+                };
+
+                match n & 0x7 {
+                    0x0 => self.d = operand,
+                    0x1 => self.d |= operand,
+                    0x2 => self.d &= operand,
+                    0x3 => self.d ^= operand,
+                    0x4 => self.add(operand, self.d, false),
+                    0x5 => self.add(operand, !self.d, true),
+                    0x6 if n == 0x6 => {
+                        self.df = self.d & 1 != 0;
+                        self.d >>= 1;
+                    }
+                    0x6 => {
+                        self.df = self.d & 0x80 != 0;
+                        self.d <<= 1;
+                    }
+                    _ => self.add(self.d, !operand, true),
+                }
+            }
+        }
+
+        SHORT_CYCLES
+    }
+}
+
+fn read(memory: &[u8], address: u16) -> u8 {
+    memory[address as usize & (memory.len() - 1)]
+}
+
+fn write(memory: &mut [u8], address: u16, value: u8) {
+    let size = memory.len();
+    memory[address as usize & (size - 1)] = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// D and DF after executing `opcode` with D, DF and an operand of `m`, which is both at R(X)
+    /// and right after the opcode for the immediate instructions
+    fn arithmetic(opcode: u8, d: u8, df: bool, m: u8) -> (u8, bool) {
+        let mut cpu = Cpu::new();
+        let mut memory = [0; 0x200];
+        memory[..2].copy_from_slice(&[opcode, m]);
+        memory[0x100] = m;
+
+        cpu.d = d;
+        cpu.df = df;
+        cpu.x = 1;
+        cpu.registers[1] = 0x100;
+
+        assert_eq!(cpu.step(&mut memory), SHORT_CYCLES);
+        (cpu.d, cpu.df)
+    }
+
+    #[test]
+    fn add_with_carry() {
+        for opcode in [0x74, 0x7C] {
+            assert_eq!(arithmetic(opcode, 0x12, false, 0x34), (0x46, false));
+            assert_eq!(arithmetic(opcode, 0x12, true, 0x34), (0x47, false));
+            assert_eq!(arithmetic(opcode, 0x80, true, 0x80), (0x01, true));
+            assert_eq!(arithmetic(opcode, 0xFF, true, 0x00), (0x00, true));
+        }
+    }
+
+    #[test]
+    fn shift_right_with_carry() {
+        assert_eq!(arithmetic(0x76, 0x81, false, 0), (0x40, true));
+        assert_eq!(arithmetic(0x76, 0x02, true, 0), (0x81, false));
+        assert_eq!(arithmetic(0x76, 0x01, true, 0), (0x80, true));
+    }
+
+    #[test]
+    fn subtract_with_borrow() {
+        // DF is clear when there's a borrow, and a clear DF borrows one more
+        for opcode in [0x75, 0x7D] {
+            // SDB is M(R(X)) - D
+            assert_eq!(arithmetic(opcode, 0x10, true, 0x20), (0x10, true));
+            assert_eq!(arithmetic(opcode, 0x10, false, 0x20), (0x0F, true));
+            assert_eq!(arithmetic(opcode, 0x20, true, 0x10), (0xF0, false));
+            assert_eq!(arithmetic(opcode, 0x00, false, 0x00), (0xFF, false));
+        }
+
+        for opcode in [0x77, 0x7F] {
+            // SMB is D - M(R(X))
+            assert_eq!(arithmetic(opcode, 0x20, true, 0x10), (0x10, true));
+            assert_eq!(arithmetic(opcode, 0x20, false, 0x10), (0x0F, true));
+            assert_eq!(arithmetic(opcode, 0x10, false, 0x10), (0xFF, false));
+            assert_eq!(arithmetic(opcode, 0x10, true, 0x10), (0x00, true));
+        }
+    }
+
+    #[test]
+    fn mark_and_return() {
+        let mut cpu = Cpu::new();
+        let mut memory = [0; 0x200];
+        // MARK, INC R2, SEX 2 and RET
+        memory[0x10..0x14].copy_from_slice(&[0x79, 0x12, 0xE2, 0x70]);
+
+        cpu.ie = false;
+        cpu.p = 5;
+        cpu.x = 3;
+        cpu.registers[2] = 0x100;
+        cpu.registers[5] = 0x10;
+
+        cpu.step(&mut memory);
+        assert_eq!(cpu.t, 0x35);
+        assert_eq!(memory[0x100], 0x35);
+        assert_eq!((cpu.x, cpu.p), (5, 5));
+        assert_eq!(cpu.registers[2], 0xFF);
+
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!((cpu.x, cpu.p), (3, 5));
+        assert_eq!(cpu.registers[2], 0x101);
+        assert_eq!(cpu.registers[5], 0x14);
+        assert!(cpu.ie);
+    }
+}
@@ -0,0 +1,212 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::ControlFlow;
+
+use super::chip8;
+use super::interfaces;
+use super::state;
+use super::BackendError;
+use super::CpuState;
+use super::Instruction;
+use super::Machine;
+
+/// CHIP-8 as reimplemented for the HP 48 calculators, which SUPER-CHIP was later built on
+pub struct Backend {
+    inner: chip8::Backend,
+}
+
+impl Backend {
+    pub(super) fn execute(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+        keyboard_state: &mut interfaces::KeypadState,
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let control_flow = self.inner.execute(index, instruction, keyboard_state)?;
+
+        // SAVE and LOAD increment the address register by X instead of X + 1
+        if instruction.operator_code() == 0xF
+            && matches!(instruction.operand_nn(), 0x55 | 0x65)
+            && self.inner.options.increment_address
+        {
+            self.inner.registers.address -= 1;
+        }
+
+        Ok(control_flow)
+    }
+
+    pub fn new(
+        options: super::Options,
+        display_options: Option<interfaces::DisplayOptions>,
+    ) -> Self {
+        Self {
+            inner: chip8::Backend::new(options, display_options),
+        }
+    }
+}
+
+impl Machine for Backend {
+    #[inline]
+    fn cpu_state(&self) -> CpuState<'_> {
+        self.inner.cpu_state()
+    }
+
+    #[inline]
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError> {
+        self.inner.display_buffer()
+    }
+
+    #[inline]
+    fn display_buffer_size(&self) -> [usize; 2] {
+        self.inner.display_buffer_size()
+    }
+
+    #[inline]
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions> {
+        self.inner.display_options_mut()
+    }
+
+    #[inline]
+    fn is_display_buffer_dirty(&self) -> bool {
+        self.inner.is_display_buffer_dirty()
+    }
+
+    #[inline]
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
+        self.inner.load(font, program)
+    }
+
+    #[inline]
+    fn options_mut(&mut self) -> &mut super::Options {
+        &mut self.inner.options
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    #[inline]
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        self.inner.restore_state(reader)
+    }
+
+    #[inline]
+    fn save_state(&self, writer: &mut state::Writer) {
+        self.inner.save_state(writer);
+    }
+
+    #[inline]
+    fn seed(&self) -> u64 {
+        self.inner.seed()
+    }
+
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn step(
+        &mut self,
+        keyboard_state: &mut interfaces::KeypadState,
+        _persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let (index, instruction) = self.inner.fetch()?;
+        self.execute(index, instruction, keyboard_state)
+    }
+
+    #[inline]
+    fn timers(&self) -> &super::Timers {
+        &self.inner.timers
+    }
+
+    #[inline]
+    fn timers_mut(&mut self) -> &mut super::Timers {
+        &mut self.inner.timers
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new(
+            super::Options {
+                copy_and_shift: false,
+                display_wait: false,
+                increment_address: true,
+                machine_code: false,
+                quirky_jump: true,
+                reset_flag: false,
+            },
+            Some(interfaces::DisplayOptions {
+                clip_sprites: true,
+                half_pixel_scrolling: Default::default(),
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut backend: Backend, program: &[u8]) -> Backend {
+        backend.load(None, program).unwrap();
+
+        for _ in 0..program.len() / 2 {
+            let _ = backend
+                .step(&mut interfaces::KeypadState::new(), &mut [])
+                .unwrap();
+        }
+
+        backend
+    }
+
+    #[test]
+    fn default_quirks() {
+        let mut backend = Backend::default();
+        let options = backend.options_mut();
+
+        assert!(!options.copy_and_shift);
+        assert!(!options.display_wait);
+        assert!(options.increment_address);
+        assert!(!options.machine_code);
+        assert!(options.quirky_jump);
+        assert!(!options.reset_flag);
+
+        // shifts work on vX alone
+        let backend = run(Backend::default(), &[0x61, 0x03, 0x62, 0x08, 0x81, 0x26]);
+        assert_eq!(backend.inner.registers.general[1], 1);
+
+        // BXNN jumps to XNN plus vX
+        let backend = run(Backend::default(), &[0x62, 0x10, 0xB2, 0x34]);
+        assert_eq!(backend.inner.index, 0x244);
+    }
+
+    #[test]
+    fn increment_by_x() {
+        for (opcode, address) in [(0xF2, 0x302), (0xF0, 0x300), (0xFF, 0x30F)] {
+            for nn in [0x55, 0x65] {
+                let backend = run(Backend::default(), &[0xA3, 0x00, opcode, nn]);
+                assert_eq!(backend.inner.registers.address, address);
+            }
+        }
+
+        let mut backend = Backend::default();
+        backend.options_mut().increment_address = false;
+        let backend = run(backend, &[0xA3, 0x00, 0xF2, 0x55]);
+        assert_eq!(backend.inner.registers.address, 0x300);
+    }
+}
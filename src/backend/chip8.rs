@@ -16,31 +16,63 @@
 use std::mem;
 use std::ops::ControlFlow;
 
-use crate::defaults;
-
+use super::cdp1802;
+use super::defaults;
 use super::interfaces;
+use super::random;
+use super::state;
 use super::BackendError;
 use super::BackendErrorKind;
+use super::CpuState;
 use super::Instruction;
+use super::Machine;
 
 pub const DISPLAY_BUFFER_ASPECT_RATIO: f32 = (DISPLAY_BUFFER_WIDTH / DISPLAY_BUFFER_HEIGHT) as f32;
 pub const DISPLAY_BUFFER_HEIGHT: usize = 32;
 pub const DISPLAY_BUFFER_WIDTH: usize = 64;
 pub const FONT_SIZE: usize = CHARACTER_SIZE * super::KEY_COUNT;
 
+pub const MEMORY_PADDING: usize = 512;
+
 pub(super) const CHARACTER_SIZE: usize = 5;
-const MEMORY_PADDING: usize = 512;
 const MEMORY_SIZE: usize = 4096;
+/// Machine cycles a machine code subroutine may run for before it's taken to be stuck, a second of
+/// the COSMAC VIP
+const MACHINE_CODE_TIMEOUT: u32 = VIP_CYCLES_PER_TICK as u32 * super::TICKS_PER_SECOND as u32;
 const REGISTER_COUNT: usize = 16;
 const STACK_SIZE: usize = 16;
 
+/// Machine cycles of the 1802 in a tick of the COSMAC VIP, which runs at 1.76 MHz with 8 clock
+/// cycles per machine cycle
+const VIP_CYCLES_PER_TICK: i32 = 3668;
+/// Where the COSMAC VIP keeps the display in memory
+const VIP_DISPLAY_ADDRESS: usize = 0xF00;
+/// Machine cycles of a tick that the display DMA steals, 8 bytes for each of the 128 scanlines
+const VIP_DISPLAY_CYCLES: i32 = 1024;
+/// Machine cycles it takes the interpreter to fetch and decode an instruction
+const VIP_FETCH_CYCLES: i32 = 40;
+/// Machine cycles of the interrupt routine, which updates the timers
+const VIP_INTERRUPT_CYCLES: i32 = 46;
+/// Extra machine cycles a skipping instruction takes when it skips
+const VIP_SKIP_CYCLES: i32 = 4;
+/// Top of the stack of the 1802 on the COSMAC VIP, right below the variables
+const VIP_STACK_ADDRESS: usize = 0xECF;
+/// Where the COSMAC VIP keeps the general purpose registers in memory
+const VIP_VARIABLES_ADDRESS: usize = 0xEF0;
+
 pub struct Backend {
+    /// Machine cycles that are left of the current tick when running with the COSMAC VIP timing,
+    /// negative if the last instruction ran past the end of the tick
+    pub(super) cycles: Option<i32>,
     pub(super) display_buffer:
         Option<interfaces::DisplayBuffer<DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT>>,
+    /// Address execution starts at
+    pub(super) entry_point: usize,
     pub(super) index: usize,
     pub(super) loaded: bool,
-    pub(super) memory: [u8; MEMORY_SIZE],
+    pub(super) memory: Vec<u8>,
     pub options: super::Options,
+    pub(super) random: random::Random,
     pub(super) registers: Registers,
     pub(super) stack: Vec<u16>,
     pub timers: super::Timers,
@@ -52,6 +84,62 @@ pub(super) struct Registers {
 }
 
 impl Backend {
+    /// Call the machine code subroutine at `address` the way the COSMAC VIP interpreter does,
+    /// returning the machine cycles it took
+    ///
+    /// The registers and the display are put where the VIP keeps them in memory for the subroutine
+    /// and read back once it returns to the interpreter with `SEP R4`
+    fn call_machine_code(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+    ) -> Result<u32, BackendError> {
+        let display_size = DISPLAY_BUFFER_WIDTH * DISPLAY_BUFFER_HEIGHT / 8;
+
+        self.memory[VIP_VARIABLES_ADDRESS..VIP_VARIABLES_ADDRESS + REGISTER_COUNT]
+            .copy_from_slice(&self.registers.general);
+        if let Some(ref display_buffer) = self.display_buffer {
+            self.memory[VIP_DISPLAY_ADDRESS..VIP_DISPLAY_ADDRESS + display_size]
+                .copy_from_slice(&display_buffer.to_bitmap());
+        }
+
+        let mut cpu = cdp1802::Cpu::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.registers[2] = VIP_STACK_ADDRESS as u16;
+        cpu.registers[3] = instruction.operand_nnn() as u16;
+        cpu.registers[5] = self.index as u16;
+        cpu.registers[8] = u16::from_be_bytes([self.timers.delay, self.timers.sound]);
+        cpu.registers[0xA] = self.registers.address as u16;
+        cpu.registers[0xB] = VIP_DISPLAY_ADDRESS as u16;
+
+        let mut cycles = 0;
+        while cpu.p != 4 {
+            if cycles >= MACHINE_CODE_TIMEOUT {
+                return Err(BackendError {
+                    instruction: Some((index, Some(instruction))),
+                    kind: BackendErrorKind::SubroutineTimeout,
+                });
+            }
+
+            cycles += cpu.step(&mut self.memory);
+        }
+
+        self.registers.general.copy_from_slice(
+            &self.memory[VIP_VARIABLES_ADDRESS..VIP_VARIABLES_ADDRESS + REGISTER_COUNT],
+        );
+        if let Some(ref mut display_buffer) = self.display_buffer {
+            display_buffer
+                .set_bitmap(&self.memory[VIP_DISPLAY_ADDRESS..VIP_DISPLAY_ADDRESS + display_size]);
+        }
+
+        [self.timers.delay, self.timers.sound] = cpu.registers[8].to_be_bytes();
+        self.index = cpu.registers[5] as usize & (self.memory.len() - 1);
+        self.registers.address = cpu.registers[0xA] as usize & (self.memory.len() - 1);
+
+        Ok(cycles)
+    }
+
     pub(super) fn execute(
         &mut self,
         index: usize,
@@ -82,7 +170,15 @@ impl Backend {
                     };
                 }
 
-                // Not implementing 0NNN, needs a 1802 or M6800 VM.
+                _ if self.options.machine_code => {
+                    let cycles = self.call_machine_code(index, instruction)?;
+
+                    if let Some(ref mut vip_cycles) = self.cycles {
+                        *vip_cycles -= cycles as i32;
+                    }
+                }
+
+                // 0NNN is a call to machine code, which is ignored unless it's enabled
                 _ => {}
             },
 
@@ -241,7 +337,7 @@ impl Backend {
 
             0xC => {
                 self.registers.general[instruction.operand_x()] =
-                    rand::random::<u8>() & instruction.operand_nn();
+                    self.random.next_u8() & instruction.operand_nn();
             }
 
             0xD => {
@@ -270,7 +366,9 @@ impl Backend {
 
                 self.registers.general[15] = (colliding_rows > 0) as u8;
 
-                return Ok(ControlFlow::Break(()));
+                if self.options.display_wait {
+                    return Ok(ControlFlow::Break(()));
+                }
             }
 
             0xE => match instruction.operand_nn() {
@@ -333,7 +431,7 @@ impl Backend {
                 0x1E => {
                     self.registers.address = (self.registers.address
                         + self.registers.general[instruction.operand_x()] as usize)
-                        & 0xFFF
+                        & (self.memory.len() - 1)
                 }
 
                 0x29 => {
@@ -421,34 +519,168 @@ impl Backend {
         Ok(ControlFlow::Continue(()))
     }
 
-    pub fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
-        if program.len() > MEMORY_SIZE - MEMORY_PADDING {
+    /// Fetch the instruction at the program counter and move past it
+    pub(super) fn fetch(&mut self) -> Result<(usize, Instruction), BackendError> {
+        if !self.loaded {
             return Err(BackendError {
                 instruction: None,
-                kind: BackendErrorKind::ProgramInvalid,
+                kind: BackendErrorKind::ProgramNotLoaded,
             });
         }
 
-        self.memory[..FONT_SIZE]
-            .copy_from_slice(&font.unwrap_or(&defaults::BACKEND_FONT)[..FONT_SIZE]);
+        if self.index + 1 >= self.memory.len() {
+            return Err(BackendError {
+                instruction: Some((self.index, None)),
+                kind: BackendErrorKind::MemoryOverflow,
+            });
+        }
 
-        self.memory[MEMORY_PADDING..MEMORY_PADDING + program.len()].copy_from_slice(program);
-        self.loaded = true;
+        let index = self.index;
+        let instruction = Instruction::new([self.memory[index], self.memory[index + 1]]);
+        self.index += mem::size_of::<Instruction>();
 
-        Ok(())
+        Ok((index, instruction))
     }
 
     pub fn new(
         options: super::Options,
         display_options: Option<interfaces::DisplayOptions>,
+    ) -> Self {
+        Self::with_memory_size(options, display_options, MEMORY_SIZE)
+    }
+
+    /// Restore the core and then the sections of the variant that wraps it with `restore`, which
+    /// mustn't change anything unless it succeeds, the core is put back if it fails
+    pub(super) fn restore_state_then(
+        &mut self,
+        reader: &mut state::Reader,
+        restore: impl FnOnce(&mut state::Reader) -> Result<(), BackendError>,
+    ) -> Result<(), BackendError> {
+        let mut backup = state::Writer::new();
+        self.save_state(&mut backup);
+
+        self.restore_state(reader)?;
+
+        if let Err(error) = restore(reader) {
+            self.restore_state(&mut state::Reader::new(&backup.into_inner()))?;
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    /// Restore a snapshot, leaving the display buffer changed if a later section is invalid
+    fn restore_state_unchecked(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        let memory = reader.read_bytes()?;
+        let index = reader.read_u32()? as usize;
+        let address = reader.read_u32()? as usize;
+        let general = reader.read_exact(REGISTER_COUNT)?;
+
+        let stack_size = reader.read_u8()? as usize;
+        if stack_size > STACK_SIZE {
+            return Err(state::invalid());
+        }
+
+        let mut stack = Vec::with_capacity(STACK_SIZE);
+        for _ in 0..stack_size {
+            stack.push(reader.read_u16()?);
+        }
+
+        let delay = reader.read_u8()?;
+        let sound = reader.read_u8()?;
+        let loaded = reader.read_bool()?;
+
+        if memory.len() != self.memory.len() || reader.read_bool()? != self.display_buffer.is_some()
+        {
+            return Err(state::invalid());
+        }
+
+        if let Some(ref mut display_buffer) = self.display_buffer {
+            display_buffer.restore_state(reader)?;
+        }
+
+        self.random.restore_state(reader)?;
+
+        if reader.read_bool()? != self.cycles.is_some() {
+            return Err(state::invalid());
+        }
+        if self.cycles.is_some() {
+            self.cycles = Some(reader.read_u32()? as i32);
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.index = index;
+        self.registers.address = address;
+        self.registers.general.copy_from_slice(general);
+        self.stack = stack;
+        self.timers.delay = delay;
+        self.timers.sound = sound;
+        self.loaded = loaded;
+
+        Ok(())
+    }
+
+    /// A backend that runs at the speed of the COSMAC VIP, ignoring the instructions per tick
+    pub fn vip() -> Self {
+        Self {
+            cycles: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Machine cycles the COSMAC VIP interpreter takes to execute an instruction, not counting the
+    /// extra cycles of skipping
+    fn vip_cycles(&self, instruction: Instruction) -> i32 {
+        let x = self.registers.general[instruction.operand_x()];
+
+        VIP_FETCH_CYCLES
+            + match instruction.operator_code() {
+                0x0 => match instruction.operand_nnn() {
+                    // the display memory is cleared a byte at a time
+                    0x0E0 => 24 + 3078,
+                    0x0EE => 10,
+                    _ => 0,
+                },
+                0x1 | 0xA => 12,
+                0x2 => 26,
+                0x3 | 0x4 => 10,
+                0x5 | 0x9 | 0xE => 14,
+                0x6 => 6,
+                0x7 => 10,
+                0x8 if instruction.operand_n() == 0x0 => 12,
+                0x8 => 44,
+                0xB => 22,
+                0xC => 36,
+                // every row of the sprite is shifted into place a bit at a time
+                0xD => 26 + instruction.operand_n() as i32 * (46 + 8 * (x % 8) as i32),
+                0xF => match instruction.operand_nn() {
+                    0x07 | 0x15 | 0x18 => 10,
+                    0x0A => 18,
+                    0x1E | 0x29 => 16,
+                    // the digits are counted by repeated subtraction
+                    0x33 => 84 + 16 * (x / 100 + x / 10 % 10 + x % 10) as i32,
+                    0x55 | 0x65 => 14 + 14 * (instruction.operand_x() as i32 + 1),
+                    _ => 0,
+                },
+                _ => 0,
+            }
+    }
+
+    pub(super) fn with_memory_size(
+        options: super::Options,
+        display_options: Option<interfaces::DisplayOptions>,
+        memory_size: usize,
     ) -> Self {
         Self {
+            cycles: None,
             display_buffer: display_options
                 .and_then(|options| Some(interfaces::DisplayBuffer::new(options))),
+            entry_point: MEMORY_PADDING,
             index: MEMORY_PADDING,
             loaded: false,
-            memory: [0; MEMORY_SIZE],
+            memory: vec![0; memory_size],
             options,
+            random: Default::default(),
             registers: Registers {
                 address: 0,
                 general: [0; REGISTER_COUNT],
@@ -457,9 +689,90 @@ impl Backend {
             timers: super::Timers { delay: 0, sound: 0 },
         }
     }
+}
+
+impl Machine for Backend {
+    fn begin_tick(&mut self) {
+        self.timers.decrement();
+
+        if let Some(ref mut cycles) = self.cycles {
+            *cycles += VIP_CYCLES_PER_TICK - VIP_DISPLAY_CYCLES - VIP_INTERRUPT_CYCLES;
+        }
+    }
 
-    pub fn reset(&mut self) {
-        self.index = MEMORY_PADDING;
+    fn cpu_state(&self) -> CpuState<'_> {
+        CpuState {
+            address: self.registers.address,
+            general: &self.registers.general,
+            index: self.index,
+            memory: &self.memory,
+            stack: &self.stack,
+            timers: &self.timers,
+        }
+    }
+
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError> {
+        match self.display_buffer {
+            Some(ref mut display_buffer) => Ok(Box::new(display_buffer.get_flattened())),
+            None => Err(BackendError {
+                instruction: None,
+                kind: BackendErrorKind::DisplayNotConnected,
+            }),
+        }
+    }
+
+    fn display_buffer_size(&self) -> [usize; 2] {
+        [DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT]
+    }
+
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions> {
+        self.display_buffer
+            .as_mut()
+            .map(|display_buffer| &mut display_buffer.options)
+    }
+
+    fn is_display_buffer_dirty(&self) -> bool {
+        self.display_buffer
+            .as_ref()
+            .is_some_and(|display_buffer| display_buffer.is_dirty())
+    }
+
+    fn is_tick_over(&self, executed: u16, n: u16) -> bool {
+        match self.cycles {
+            Some(cycles) => cycles <= 0,
+            None => executed >= n,
+        }
+    }
+
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
+        if program.len() > self.memory.len() - MEMORY_PADDING {
+            return Err(BackendError {
+                instruction: None,
+                kind: BackendErrorKind::ProgramInvalid,
+            });
+        }
+
+        self.memory[..FONT_SIZE].copy_from_slice(&font.unwrap_or(&defaults::FONT)[..FONT_SIZE]);
+
+        self.memory[MEMORY_PADDING..MEMORY_PADDING + program.len()].copy_from_slice(program);
+        self.loaded = true;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn options_mut(&mut self) -> &mut super::Options {
+        &mut self.options
+    }
+
+    fn reset(&mut self) {
+        if let Some(ref mut cycles) = self.cycles {
+            *cycles = 0;
+        }
+
+        self.index = self.entry_point;
+
+        self.random.reset();
 
         self.registers.address = 0;
         self.registers.general.fill(0);
@@ -468,45 +781,101 @@ impl Backend {
 
         self.timers.delay = 0;
         self.timers.sound = 0;
+
+        if let Some(ref mut display_buffer) = self.display_buffer {
+            display_buffer.clear();
+        }
     }
 
-    pub fn tick(
-        &mut self,
-        n: u8,
-        keyboard_state: &mut interfaces::KeypadState,
-    ) -> Result<(), BackendError> {
-        if !self.loaded {
-            return Err(BackendError {
-                instruction: None,
-                kind: BackendErrorKind::ProgramNotLoaded,
-            });
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        let mut backup = state::Writer::new();
+        self.save_state(&mut backup);
+
+        // the display buffer is restored before the sections that follow it are read
+        if let Err(error) = self.restore_state_unchecked(reader) {
+            self.restore_state_unchecked(&mut state::Reader::new(&backup.into_inner()))?;
+            return Err(error);
         }
 
-        self.timers.delay = self.timers.delay.saturating_sub(1);
-        self.timers.sound = self.timers.sound.saturating_sub(1);
+        Ok(())
+    }
 
-        for _ in 0..n {
-            if self.index + 1 >= self.memory.len() {
-                return Err(BackendError {
-                    instruction: Some((self.index, None)),
-                    kind: BackendErrorKind::MemoryOverflow,
-                });
-            }
+    fn save_state(&self, writer: &mut state::Writer) {
+        writer.write_bytes(&self.memory);
+        writer.write_u32(self.index as u32);
+        writer.write_u32(self.registers.address as u32);
+        self.registers
+            .general
+            .iter()
+            .for_each(|&register| writer.write_u8(register));
+
+        writer.write_u8(self.stack.len() as u8);
+        self.stack
+            .iter()
+            .for_each(|&address| writer.write_u16(address));
+
+        writer.write_u8(self.timers.delay);
+        writer.write_u8(self.timers.sound);
+        writer.write_bool(self.loaded);
+
+        writer.write_bool(self.display_buffer.is_some());
+        if let Some(ref display_buffer) = self.display_buffer {
+            display_buffer.save_state(writer);
+        }
+
+        self.random.save_state(writer);
+
+        writer.write_bool(self.cycles.is_some());
+        if let Some(cycles) = self.cycles {
+            writer.write_u32(cycles as u32);
+        }
+    }
 
-            let instruction =
-                Instruction::new([self.memory[self.index], self.memory[self.index + 1]]);
+    #[inline]
+    fn seed(&self) -> u64 {
+        self.random.seed()
+    }
 
-            let last_index = self.index;
-            self.index += mem::size_of::<Instruction>();
+    fn set_seed(&mut self, seed: u64) {
+        self.random = random::Random::new(seed);
+    }
 
-            let control_flow = self.execute(last_index, instruction, keyboard_state)?;
+    fn step(
+        &mut self,
+        keyboard_state: &mut interfaces::KeypadState,
+        _persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let (index, instruction) = self.fetch()?;
 
-            if control_flow.is_break() {
-                break;
-            }
+        if self.cycles.is_none() {
+            return self.execute(index, instruction, keyboard_state);
         }
 
-        Ok(())
+        let cost = self.vip_cycles(instruction);
+        let next_index = self.index;
+        let control_flow = self.execute(index, instruction, keyboard_state)?;
+
+        let skipped = matches!(instruction.operator_code(), 0x3 | 0x4 | 0x5 | 0x9 | 0xE)
+            && self.index != next_index;
+        let cost = cost + if skipped { VIP_SKIP_CYCLES } else { 0 };
+
+        // the rest of the tick is spent waiting for the interrupt
+        self.cycles = self.cycles.map(|cycles| match control_flow {
+            ControlFlow::Break(()) => (cycles - cost).min(0),
+            ControlFlow::Continue(()) => cycles - cost,
+        });
+
+        Ok(control_flow)
+    }
+
+    #[inline]
+    fn timers(&self) -> &super::Timers {
+        &self.timers
+    }
+
+    #[inline]
+    fn timers_mut(&mut self) -> &mut super::Timers {
+        &mut self.timers
     }
 }
 
@@ -515,7 +884,9 @@ impl Default for Backend {
         Self::new(
             super::Options {
                 copy_and_shift: true,
+                display_wait: true,
                 increment_address: true,
+                machine_code: false,
                 quirky_jump: false,
                 reset_flag: true,
             },
@@ -526,3 +897,81 @@ impl Default for Backend {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(mut backend: Backend, program: &[u8]) -> Backend {
+        backend.load(None, program).unwrap();
+        backend
+    }
+
+    /// Machine cycles the first instruction of `program` takes on the COSMAC VIP
+    fn step_cycles(program: &[u8]) -> i32 {
+        let mut backend = loaded(Backend::vip(), program);
+
+        let _ = backend
+            .step(&mut interfaces::KeypadState::new(), &mut [])
+            .unwrap();
+        -backend.cycles.unwrap()
+    }
+
+    #[test]
+    fn vip_cycles() {
+        let mut backend = Backend::vip();
+        backend.registers.general[0x3] = 123;
+        backend.registers.general[0x5] = 13;
+
+        for (opcode, cycles) in [
+            (0x00E0, 3142),
+            (0x6012, 46),
+            (0x8014, 84),
+            // 5 rows shifted by 5 bits
+            (0xD505, 66 + 5 * (46 + 40)),
+            // 1, 2 and 3 subtracted to count the digits of 123
+            (0xF333, 124 + 16 * 6),
+            (0xF265, 54 + 14 * 3),
+        ] {
+            let instruction = Instruction::new(u16::to_be_bytes(opcode));
+            assert_eq!(backend.vip_cycles(instruction), cycles, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn vip_skip_cycles() {
+        // v0 is 0, so 3000 and 4001 skip and 3001 and 4000 don't
+        assert_eq!(step_cycles(&[0x30, 0x00]), 50 + VIP_SKIP_CYCLES);
+        assert_eq!(step_cycles(&[0x40, 0x01]), 50 + VIP_SKIP_CYCLES);
+        assert_eq!(step_cycles(&[0x30, 0x01]), 50);
+        assert_eq!(step_cycles(&[0x40, 0x00]), 50);
+    }
+
+    #[test]
+    fn display_wait() {
+        let program = [0xD0, 0x01];
+
+        let mut backend = loaded(Backend::default(), &program);
+        assert!(backend.options.display_wait);
+        assert!(matches!(
+            backend.step(&mut interfaces::KeypadState::new(), &mut []),
+            Ok(ControlFlow::Break(()))
+        ));
+
+        backend.reset();
+        backend.options.display_wait = false;
+        assert!(matches!(
+            backend.step(&mut interfaces::KeypadState::new(), &mut []),
+            Ok(ControlFlow::Continue(()))
+        ));
+
+        // the rest of the tick is spent waiting
+        let mut backend = loaded(Backend::vip(), &program);
+        backend.begin_tick();
+        assert!(matches!(
+            backend.step(&mut interfaces::KeypadState::new(), &mut []),
+            Ok(ControlFlow::Break(()))
+        ));
+        assert!(backend.is_tick_over(1, u16::MAX));
+    }
+}
@@ -0,0 +1,370 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::mem;
+
+use super::Instruction;
+
+const COMMENT_COLUMN: usize = 28;
+const INDENTATION: &str = "    ";
+
+/// Opcodes a variant understands, every set includes the ones before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Lowercase mnemonics from Cowgod's technical reference, `ld v3, 0x20`
+    Cowgod,
+    /// Octo's assembly language, `v3 := 0x20`
+    Octo,
+}
+
+impl Syntax {
+    fn comment(&self) -> char {
+        match self {
+            Self::Cowgod => ';',
+            Self::Octo => '#',
+        }
+    }
+}
+
+/// Disassemble the instruction at the start of `bytes`, returns its text and length in bytes
+///
+/// Unrecognized instructions are written out as data. Jump and call targets found in `labels`
+/// are referred to by label instead of address, `bytes` must not be empty
+pub fn decode(
+    bytes: &[u8],
+    instruction_set: InstructionSet,
+    syntax: Syntax,
+    labels: &BTreeSet<usize>,
+) -> (String, usize) {
+    let length = length(bytes, instruction_set);
+
+    if length == 4 {
+        let address = u16::from_be_bytes([bytes[2], bytes[3]]);
+        return match syntax {
+            Syntax::Cowgod => (format!("ld i, long 0x{:04X}", address), length),
+            Syntax::Octo => (format!("i := long 0x{:04X}", address), length),
+        };
+    }
+
+    let text = match bytes {
+        [high, low, ..] => {
+            let instruction = Instruction::new([*high, *low]);
+
+            match syntax {
+                Syntax::Cowgod => cowgod(instruction, instruction_set, labels),
+                Syntax::Octo => octo(instruction, instruction_set, labels),
+            }
+        }
+        _ => None,
+    };
+
+    (
+        text.unwrap_or_else(|| data(&bytes[..length], syntax)),
+        length,
+    )
+}
+
+/// Disassemble a program loaded at `origin` into a listing, one instruction per line
+///
+/// The instructions are decoded one after the other, so data mixed with code shows up as
+/// (mostly nonsensical) instructions. Every jump and call target gets a label
+pub fn disassemble(
+    program: &[u8],
+    origin: usize,
+    instruction_set: InstructionSet,
+    syntax: Syntax,
+) -> String {
+    let mut starts = BTreeSet::new();
+    let mut targets = BTreeSet::new();
+
+    let mut offset = 0;
+    while offset < program.len() {
+        starts.insert(origin + offset);
+
+        if let [high, low, ..] = program[offset..] {
+            targets.extend(target(Instruction::new([high, low])));
+        }
+
+        offset += length(&program[offset..], instruction_set);
+    }
+
+    let labels: BTreeSet<usize> = starts.intersection(&targets).copied().collect();
+    let mut listing = String::new();
+
+    let mut offset = 0;
+    while offset < program.len() {
+        let address = origin + offset;
+
+        if labels.contains(&address) {
+            let _ = match syntax {
+                Syntax::Cowgod => writeln!(listing, "{}:", label(address)),
+                Syntax::Octo => writeln!(listing, ": {}", label(address)),
+            };
+        }
+
+        let (text, length) = decode(&program[offset..], instruction_set, syntax, &labels);
+
+        let mut hex = String::new();
+        for byte in &program[offset..offset + length] {
+            let _ = write!(hex, "{:02X}", byte);
+        }
+
+        let _ = writeln!(
+            listing,
+            "{}{:<width$} {} {:03X}: {}",
+            INDENTATION,
+            text,
+            syntax.comment(),
+            address,
+            hex,
+            width = COMMENT_COLUMN - INDENTATION.len(),
+        );
+
+        offset += length;
+    }
+
+    listing
+}
+
+/// Name of the label at the address
+pub fn label(address: usize) -> String {
+    format!("L{:03X}", address)
+}
+
+fn address(address: usize, labels: &BTreeSet<usize>) -> String {
+    match labels.contains(&address) {
+        true => label(address),
+        false => format!("0x{:03X}", address),
+    }
+}
+
+fn cowgod(
+    instruction: Instruction,
+    instruction_set: InstructionSet,
+    labels: &BTreeSet<usize>,
+) -> Option<String> {
+    let n = instruction.operand_n();
+    let nn = instruction.operand_nn();
+    let nnn = instruction.operand_nnn();
+    let x = instruction.operand_x();
+    let y = instruction.operand_y();
+
+    let super_chip = instruction_set >= InstructionSet::SuperChip;
+    let xo_chip = instruction_set >= InstructionSet::XoChip;
+
+    Some(match instruction.operator_code() {
+        0x0 => match nnn {
+            0x0E0 => "cls".to_string(),
+            0x0EE => "ret".to_string(),
+            0x0C0..=0x0CF if super_chip => format!("scd {}", n),
+            0x0D0..=0x0DF if xo_chip => format!("scu {}", n),
+            0x0FB if super_chip => "scr".to_string(),
+            0x0FC if super_chip => "scl".to_string(),
+            0x0FD if super_chip => "exit".to_string(),
+            0x0FE if super_chip => "low".to_string(),
+            0x0FF if super_chip => "high".to_string(),
+            _ if !super_chip => format!("sys 0x{:03X}", nnn),
+            _ => return None,
+        },
+        0x1 => format!("jp {}", address(nnn, labels)),
+        0x2 => format!("call {}", address(nnn, labels)),
+        0x3 => format!("se v{:x}, 0x{:02X}", x, nn),
+        0x4 => format!("sne v{:x}, 0x{:02X}", x, nn),
+        0x5 => match n {
+            0x0 => format!("se v{:x}, v{:x}", x, y),
+            0x2 if xo_chip => format!("save v{:x}, v{:x}", x, y),
+            0x3 if xo_chip => format!("load v{:x}, v{:x}", x, y),
+            _ => return None,
+        },
+        0x6 => format!("ld v{:x}, 0x{:02X}", x, nn),
+        0x7 => format!("add v{:x}, 0x{:02X}", x, nn),
+        0x8 => {
+            let mnemonic = match n {
+                0x0 => "ld",
+                0x1 => "or",
+                0x2 => "and",
+                0x3 => "xor",
+                0x4 => "add",
+                0x5 => "sub",
+                0x6 => "shr",
+                0x7 => "subn",
+                0xE => "shl",
+                _ => return None,
+            };
+
+            format!("{} v{:x}, v{:x}", mnemonic, x, y)
+        }
+        0x9 if n == 0 => format!("sne v{:x}, v{:x}", x, y),
+        0xA => format!("ld i, {}", address(nnn, labels)),
+        0xB => format!("jp v0, {}", address(nnn, labels)),
+        0xC => format!("rnd v{:x}, 0x{:02X}", x, nn),
+        0xD => format!("drw v{:x}, v{:x}, {}", x, y, n),
+        0xE => match nn {
+            0x9E => format!("skp v{:x}", x),
+            0xA1 => format!("sknp v{:x}", x),
+            _ => return None,
+        },
+        0xF => match nn {
+            0x01 if xo_chip => format!("plane {}", x),
+            0x02 if xo_chip && x == 0 => "audio".to_string(),
+            0x07 => format!("ld v{:x}, dt", x),
+            0x0A => format!("ld v{:x}, k", x),
+            0x15 => format!("ld dt, v{:x}", x),
+            0x18 => format!("ld st, v{:x}", x),
+            0x1E => format!("add i, v{:x}", x),
+            0x29 => format!("ld f, v{:x}", x),
+            0x30 if super_chip => format!("ld hf, v{:x}", x),
+            0x33 => format!("ld b, v{:x}", x),
+            0x3A if xo_chip => format!("pitch v{:x}", x),
+            0x55 => format!("ld [i], v{:x}", x),
+            0x65 => format!("ld v{:x}, [i]", x),
+            0x75 if super_chip => format!("ld r, v{:x}", x),
+            0x85 if super_chip => format!("ld v{:x}, r", x),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+fn data(bytes: &[u8], syntax: Syntax) -> String {
+    let mut text = match syntax {
+        Syntax::Cowgod => ":byte".to_string(),
+        Syntax::Octo => String::new(),
+    };
+
+    for byte in bytes {
+        let _ = write!(text, " 0x{:02X}", byte);
+    }
+
+    text.trim_start().to_string()
+}
+
+/// Length of the instruction at the start of `bytes`, only XO-CHIP's `F000 NNNN` takes up more
+/// than two bytes
+fn length(bytes: &[u8], instruction_set: InstructionSet) -> usize {
+    match bytes {
+        [0xF0, 0x00, _, _, ..] if instruction_set >= InstructionSet::XoChip => {
+            2 * mem::size_of::<Instruction>()
+        }
+        _ => bytes.len().min(mem::size_of::<Instruction>()),
+    }
+}
+
+fn octo(
+    instruction: Instruction,
+    instruction_set: InstructionSet,
+    labels: &BTreeSet<usize>,
+) -> Option<String> {
+    let n = instruction.operand_n();
+    let nn = instruction.operand_nn();
+    let nnn = instruction.operand_nnn();
+    let x = instruction.operand_x();
+    let y = instruction.operand_y();
+
+    let super_chip = instruction_set >= InstructionSet::SuperChip;
+    let xo_chip = instruction_set >= InstructionSet::XoChip;
+
+    Some(match instruction.operator_code() {
+        0x0 => match nnn {
+            0x0E0 => "clear".to_string(),
+            0x0EE => "return".to_string(),
+            0x0C0..=0x0CF if super_chip => format!("scroll-down {}", n),
+            0x0D0..=0x0DF if xo_chip => format!("scroll-up {}", n),
+            0x0FB if super_chip => "scroll-right".to_string(),
+            0x0FC if super_chip => "scroll-left".to_string(),
+            0x0FD if super_chip => "exit".to_string(),
+            0x0FE if super_chip => "lores".to_string(),
+            0x0FF if super_chip => "hires".to_string(),
+            _ => return None,
+        },
+        0x1 => format!("jump {}", address(nnn, labels)),
+        0x2 => match labels.contains(&nnn) {
+            true => label(nnn),
+            false => format!(":call 0x{:03X}", nnn),
+        },
+        0x3 => format!("if v{:x} != 0x{:02X} then", x, nn),
+        0x4 => format!("if v{:x} == 0x{:02X} then", x, nn),
+        0x5 => match n {
+            0x0 => format!("if v{:x} != v{:x} then", x, y),
+            0x2 if xo_chip => format!("save v{:x} - v{:x}", x, y),
+            0x3 if xo_chip => format!("load v{:x} - v{:x}", x, y),
+            _ => return None,
+        },
+        0x6 => format!("v{:x} := 0x{:02X}", x, nn),
+        0x7 => format!("v{:x} += 0x{:02X}", x, nn),
+        0x8 => {
+            let operator = match n {
+                0x0 => ":=",
+                0x1 => "|=",
+                0x2 => "&=",
+                0x3 => "^=",
+                0x4 => "+=",
+                0x5 => "-=",
+                0x6 => ">>=",
+                0x7 => "=-",
+                0xE => "<<=",
+                _ => return None,
+            };
+
+            format!("v{:x} {} v{:x}", x, operator, y)
+        }
+        0x9 if n == 0 => format!("if v{:x} == v{:x} then", x, y),
+        0xA => format!("i := {}", address(nnn, labels)),
+        0xB => format!("jump0 {}", address(nnn, labels)),
+        0xC => format!("v{:x} := random 0x{:02X}", x, nn),
+        0xD => format!("sprite v{:x} v{:x} {}", x, y, n),
+        0xE => match nn {
+            0x9E => format!("if v{:x} -key then", x),
+            0xA1 => format!("if v{:x} key then", x),
+            _ => return None,
+        },
+        0xF => match nn {
+            0x01 if xo_chip => format!("plane {}", x),
+            0x02 if xo_chip && x == 0 => "audio".to_string(),
+            0x07 => format!("v{:x} := delay", x),
+            0x0A => format!("v{:x} := key", x),
+            0x15 => format!("delay := v{:x}", x),
+            0x18 => format!("buzzer := v{:x}", x),
+            0x1E => format!("i += v{:x}", x),
+            0x29 => format!("i := hex v{:x}", x),
+            0x30 if super_chip => format!("i := bighex v{:x}", x),
+            0x33 => format!("bcd v{:x}", x),
+            0x3A if xo_chip => format!("pitch := v{:x}", x),
+            0x55 => format!("save v{:x}", x),
+            0x65 => format!("load v{:x}", x),
+            0x75 if super_chip => format!("saveflags v{:x}", x),
+            0x85 if super_chip => format!("loadflags v{:x}", x),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Address a jump or call goes to
+fn target(instruction: Instruction) -> Option<usize> {
+    match instruction.operator_code() {
+        0x1 | 0x2 => Some(instruction.operand_nnn()),
+        _ => None,
+    }
+}
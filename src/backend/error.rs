@@ -32,11 +32,26 @@ pub enum BackendErrorKind {
     ProgramNotLoaded,
     StackOverflow,
     StackUnderflow,
+    StateInvalid,
+    SubroutineTimeout,
     UnrecognizedInstruction,
     UnrecognizedKey,
     UnrecognizedSprite,
 }
 
+impl BackendError {
+    /// Whether the machine can't carry on after the error
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self.kind,
+            BackendErrorKind::MemoryOverflow
+                | BackendErrorKind::ProgramInvalid
+                | BackendErrorKind::ProgramNotLoaded
+                | BackendErrorKind::DisplayNotConnected
+        )
+    }
+}
+
 impl fmt::Display for BackendError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.instruction {
@@ -63,6 +78,8 @@ impl fmt::Display for BackendErrorKind {
                 Self::ProgramNotLoaded => "attempt to run without loading any program",
                 Self::StackOverflow => "attempt to call a coroutine when the stack is full",
                 Self::StackUnderflow => "attempt to return when the stack is empty",
+                Self::StateInvalid => "attempt to restore invalid save state",
+                Self::SubroutineTimeout => "machine code subroutine didn't return",
                 Self::UnrecognizedInstruction => "unrecognized instruction",
                 Self::UnrecognizedKey => "attempt to access the state of an unrecognized key",
                 Self::UnrecognizedSprite => "attempt to load unrecognized sprite",
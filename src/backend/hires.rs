@@ -0,0 +1,267 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::ControlFlow;
+
+use super::chip8;
+use super::interfaces;
+use super::state;
+use super::BackendError;
+use super::BackendErrorKind;
+use super::CpuState;
+use super::Instruction;
+use super::Machine;
+
+pub const DISPLAY_BUFFER_ASPECT_RATIO: f32 = (DISPLAY_BUFFER_WIDTH / DISPLAY_BUFFER_HEIGHT) as f32;
+pub const DISPLAY_BUFFER_HEIGHT: usize = 64;
+pub const DISPLAY_BUFFER_WIDTH: usize = 64;
+
+/// Where the program proper starts after the header
+const ENTRY_POINT: usize = 0x2C0;
+/// First instruction of a HIRES program, which jumps into the 1802 code that sets the mode up
+const HEADER: [u8; 2] = [0x12, 0x60];
+
+/// The HIRES CHIP-8 of the COSMAC VIP, with a 64x64 display
+pub struct Backend {
+    display_buffer: interfaces::DisplayBuffer<DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT>,
+    inner: chip8::Backend,
+}
+
+impl Backend {
+    pub(super) fn execute(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+        keyboard_state: &mut interfaces::KeypadState,
+    ) -> Result<ControlFlow<()>, BackendError> {
+        match instruction.operator_code() {
+            0x0 if matches!(instruction.operand_nnn(), 0x0E0 | 0x230) => {
+                self.display_buffer.clear()
+            }
+
+            0xD => {
+                let n = instruction.operand_n() as usize;
+
+                if self.inner.registers.address + n >= self.inner.memory.len() {
+                    return Err(BackendError {
+                        instruction: Some((index, Some(instruction))),
+                        kind: BackendErrorKind::MemoryOverflow,
+                    });
+                }
+
+                let colliding_rows = self.display_buffer.draw(
+                    (
+                        self.inner.registers.general[instruction.operand_x()] as usize,
+                        self.inner.registers.general[instruction.operand_y()] as usize,
+                    ),
+                    &self.inner.memory
+                        [self.inner.registers.address..self.inner.registers.address + n],
+                );
+
+                self.inner.registers.general[15] = (colliding_rows > 0) as u8;
+
+                if self.inner.options.display_wait {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+
+            _ => return self.inner.execute(index, instruction, keyboard_state),
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    pub fn new(options: super::Options, display_options: interfaces::DisplayOptions) -> Self {
+        Self {
+            display_buffer: interfaces::DisplayBuffer::new(display_options),
+            inner: chip8::Backend::new(options, None),
+        }
+    }
+}
+
+impl Machine for Backend {
+    #[inline]
+    fn cpu_state(&self) -> CpuState<'_> {
+        self.inner.cpu_state()
+    }
+
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError> {
+        Ok(Box::new(self.display_buffer.get_flattened()))
+    }
+
+    fn display_buffer_size(&self) -> [usize; 2] {
+        [DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT]
+    }
+
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions> {
+        Some(&mut self.display_buffer.options)
+    }
+
+    #[inline]
+    fn is_display_buffer_dirty(&self) -> bool {
+        self.display_buffer.is_dirty()
+    }
+
+    /// Load the program, which starts past the header if it has one
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
+        self.inner.load(font, program)?;
+
+        self.inner.entry_point = if has_header(program) {
+            ENTRY_POINT
+        } else {
+            chip8::MEMORY_PADDING
+        };
+        self.inner.index = self.inner.entry_point;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn options_mut(&mut self) -> &mut super::Options {
+        &mut self.inner.options
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.display_buffer.clear();
+    }
+
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        self.inner
+            .restore_state_then(reader, |reader| self.display_buffer.restore_state(reader))
+    }
+
+    fn save_state(&self, writer: &mut state::Writer) {
+        self.inner.save_state(writer);
+        self.display_buffer.save_state(writer);
+    }
+
+    #[inline]
+    fn seed(&self) -> u64 {
+        self.inner.seed()
+    }
+
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn step(
+        &mut self,
+        keyboard_state: &mut interfaces::KeypadState,
+        _persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let (index, instruction) = self.inner.fetch()?;
+        self.execute(index, instruction, keyboard_state)
+    }
+
+    #[inline]
+    fn timers(&self) -> &super::Timers {
+        &self.inner.timers
+    }
+
+    #[inline]
+    fn timers_mut(&mut self) -> &mut super::Timers {
+        &mut self.inner.timers
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new(
+            super::Options {
+                copy_and_shift: true,
+                display_wait: true,
+                increment_address: true,
+                machine_code: false,
+                quirky_jump: false,
+                reset_flag: true,
+            },
+            interfaces::DisplayOptions {
+                clip_sprites: true,
+                half_pixel_scrolling: Default::default(),
+            },
+        )
+    }
+}
+
+/// Whether a program starts with the header of HIRES programs
+pub fn has_header(program: &[u8]) -> bool {
+    program.starts_with(&HEADER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::detect_variant;
+
+    fn step(backend: &mut Backend) {
+        let _ = backend
+            .step(&mut interfaces::KeypadState::new(), &mut [])
+            .unwrap();
+    }
+
+    #[test]
+    fn entry_point() {
+        let mut program = vec![0; ENTRY_POINT - chip8::MEMORY_PADDING + 2];
+        program[..2].copy_from_slice(&HEADER);
+        program[ENTRY_POINT - chip8::MEMORY_PADDING..].copy_from_slice(&[0x60, 0x42]);
+
+        let mut backend = Backend::default();
+        backend.load(None, &program).unwrap();
+        assert_eq!(backend.inner.index, ENTRY_POINT);
+
+        step(&mut backend);
+        assert_eq!(backend.inner.registers.general[0], 0x42);
+
+        backend.reset();
+        assert_eq!(backend.inner.index, ENTRY_POINT);
+
+        backend.load(None, &[0x60, 0x42]).unwrap();
+        assert_eq!(backend.inner.index, chip8::MEMORY_PADDING);
+    }
+
+    #[test]
+    fn variant_detection() {
+        assert_eq!(detect_variant(&[0x12, 0x60, 0x01, 0x7A]), Some("hires"));
+        assert_eq!(detect_variant(&[0x12, 0x00]), None);
+        assert_eq!(detect_variant(&[0x12]), None);
+        assert_eq!(detect_variant(&[]), None);
+    }
+
+    #[test]
+    fn clear() {
+        // draw the digit 0 on the bottom half of the display and clear it with 0230
+        let mut backend = Backend::default();
+        backend
+            .load(
+                None,
+                &[0x60, 0x20, 0x61, 0x28, 0xA0, 0x00, 0xD0, 0x15, 0x02, 0x30],
+            )
+            .unwrap();
+
+        for _ in 0..4 {
+            step(&mut backend);
+        }
+        let pixels: Vec<u8> = backend.display_buffer().unwrap().collect();
+        assert_eq!(pixels.len(), DISPLAY_BUFFER_WIDTH * DISPLAY_BUFFER_HEIGHT);
+        assert!(pixels[0x28 * DISPLAY_BUFFER_WIDTH..]
+            .iter()
+            .any(|&pixel| pixel != 0));
+
+        step(&mut backend);
+        assert!(backend.display_buffer().unwrap().all(|pixel| pixel == 0));
+    }
+}
@@ -16,15 +16,16 @@
 use std::iter;
 
 use bitvec::view::BitViewSized;
-use eframe::egui;
 
-use crate::defaults;
+use super::state;
+use super::BackendError;
 
 pub(super) struct DisplayBuffer<const W: usize, const H: usize> {
-    buffer: Vec<Vec<bool>>,
+    buffer: Vec<Vec<u8>>,
     dirty: bool,
     pub(super) half_resolution: bool,
     pub options: DisplayOptions,
+    pub(super) planes: u8,
 }
 
 pub struct DisplayOptions {
@@ -46,33 +47,55 @@ enum KeyState {
 impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
     pub fn get_flattened<'a>(
         &'a mut self,
-    ) -> iter::Copied<iter::Flatten<std::slice::Iter<'a, Vec<bool>>>> {
+    ) -> iter::Copied<iter::Flatten<std::slice::Iter<'a, Vec<u8>>>> {
         self.dirty = false;
         self.buffer.iter().flatten().copied()
     }
 
+    #[inline]
     pub fn clear(&mut self) {
+        self.clear_planes(self.planes);
+    }
+
+    pub fn clear_planes(&mut self, planes: u8) {
         for row in self.buffer.iter_mut() {
-            row.fill(false);
+            for pixel in row.iter_mut() {
+                *pixel &= !planes;
+            }
         }
 
         self.dirty = true;
     }
 
+    #[inline]
     pub fn draw(&mut self, coordinates: (usize, usize), sprite: &[u8]) -> usize {
+        self.draw_plane(0, coordinates, sprite)
+    }
+
+    pub fn draw_plane(
+        &mut self,
+        plane: usize,
+        coordinates: (usize, usize),
+        sprite: &[u8],
+    ) -> usize {
         if sprite.len() == 32 {
             let sprite_16x16: Vec<u16> = sprite
                 .chunks(2)
                 .map(|pair| u16::from_be_bytes(pair.try_into().unwrap()))
                 .collect();
 
-            return self.draw_internal(coordinates, &sprite_16x16);
+            return self.draw_internal(1 << plane, coordinates, &sprite_16x16);
         }
 
-        self.draw_internal(coordinates, sprite)
+        self.draw_internal(1 << plane, coordinates, sprite)
     }
 
-    fn draw_internal<B: BitViewSized + Copy>(&mut self, coordinates: (usize, usize), sprite: &[B]) -> usize {
+    fn draw_internal<B: BitViewSized + Copy>(
+        &mut self,
+        plane: u8,
+        coordinates: (usize, usize),
+        sprite: &[B],
+    ) -> usize {
         let scaling_factor = if self.half_resolution { 2 } else { 1 };
 
         let coordinates = (
@@ -107,15 +130,15 @@ impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
 
                 if *bit {
                     if !self.half_resolution {
-                        self.buffer[cy][cx] ^= true;
-                        collided |= !(self.buffer[cy][cx]);
+                        self.buffer[cy][cx] ^= plane;
+                        collided |= self.buffer[cy][cx] & plane == 0;
                         continue;
                     }
 
                     for i in cy..=cy + 1 {
                         for j in cx..=cx + 1 {
-                            self.buffer[i][j] ^= true;
-                            collided |= !(self.buffer[i][j])
+                            self.buffer[i][j] ^= plane;
+                            collided |= self.buffer[i][j] & plane == 0;
                         }
                     }
                 };
@@ -135,36 +158,66 @@ impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
 
     pub fn new(options: DisplayOptions) -> Self {
         Self {
-            buffer: vec![vec![false; W]; H],
+            buffer: vec![vec![0; W]; H],
             dirty: false,
             half_resolution: false,
             options,
+            planes: 1,
         }
     }
 
-    pub fn scroll_down(&mut self, n: usize) {
-        if n == 0 {
-            return;
-        }
-
-        let n = if self.half_resolution && !self.options.half_pixel_scrolling {
+    fn scaled(&self, n: usize) -> usize {
+        if self.half_resolution && !self.options.half_pixel_scrolling {
             2 * n
         } else {
             n
-        };
+        }
+    }
+
+    pub fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        let half_resolution = reader.read_bool()?;
+        let planes = reader.read_u8()?;
+        let pixels = reader.read_bytes()?;
 
+        if pixels.len() != W * H {
+            return Err(state::invalid());
+        }
+
+        for (row, pixels) in self.buffer.iter_mut().zip(pixels.chunks(W)) {
+            row.copy_from_slice(pixels);
+        }
+
+        self.half_resolution = half_resolution;
+        self.planes = planes;
         self.dirty = true;
 
-        for i in (0..H - n).rev() {
-            let dest = &mut self.buffer[i + n] as *mut Vec<bool>;
-            let src = &mut self.buffer[i];
+        Ok(())
+    }
+
+    pub fn save_state(&self, writer: &mut state::Writer) {
+        writer.write_bool(self.half_resolution);
+        writer.write_u8(self.planes);
+        writer.write_bytes(&self.buffer.concat());
+    }
 
-            unsafe {
-                (*dest).copy_from_slice(src);
-            }
+    pub fn scroll_down(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
+        let n = self.scaled(n).min(H);
+        let planes = self.planes;
+
+        self.dirty = true;
 
-            if i < n {
-                src.fill(false);
+        for i in (0..H).rev() {
+            for j in 0..W {
+                let pixel = if i >= n {
+                    self.buffer[i - n][j] & planes
+                } else {
+                    0
+                };
+                self.buffer[i][j] = self.buffer[i][j] & !planes | pixel;
             }
         }
     }
@@ -174,21 +227,19 @@ impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
             return;
         }
 
-        let n = if self.half_resolution && !self.options.half_pixel_scrolling {
-            2 * n
-        } else {
-            n
-        };
+        let n = self.scaled(n).min(W);
+        let planes = self.planes;
 
         self.dirty = true;
 
         for i in 0..H {
-            for j in 0..W - n {
-                self.buffer[i][j] = self.buffer[i][j + n];
-
-                if j + n > W - n {
-                    self.buffer[i][j + n] = false;
-                }
+            for j in 0..W {
+                let pixel = if j + n < W {
+                    self.buffer[i][j + n] & planes
+                } else {
+                    0
+                };
+                self.buffer[i][j] = self.buffer[i][j] & !planes | pixel;
             }
         }
     }
@@ -198,21 +249,19 @@ impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
             return;
         }
 
-        let n = if self.half_resolution && !self.options.half_pixel_scrolling {
-            2 * n
-        } else {
-            n
-        };
+        let n = self.scaled(n).min(W);
+        let planes = self.planes;
 
         self.dirty = true;
 
         for i in 0..H {
-            for j in (0..W - n).rev() {
-                self.buffer[i][j + n] = self.buffer[i][j];
-
-                if j < n {
-                    self.buffer[i][j] = false;
-                }
+            for j in (0..W).rev() {
+                let pixel = if j >= n {
+                    self.buffer[i][j - n] & planes
+                } else {
+                    0
+                };
+                self.buffer[i][j] = self.buffer[i][j] & !planes | pixel;
             }
         }
     }
@@ -222,30 +271,65 @@ impl<const W: usize, const H: usize> DisplayBuffer<W, H> {
             return;
         }
 
-        let n = if self.half_resolution && !self.options.half_pixel_scrolling {
-            2 * n
-        } else {
-            n
-        };
+        let n = self.scaled(n).min(H);
+        let planes = self.planes;
 
         self.dirty = true;
 
-        for i in 0..H - n {
-            let dest = &mut self.buffer[i] as *mut Vec<bool>;
-            let src = &mut self.buffer[i + n];
-
-            unsafe {
-                (*dest).copy_from_slice(src);
+        for i in 0..H {
+            for j in 0..W {
+                let pixel = if i + n < H {
+                    self.buffer[i + n][j] & planes
+                } else {
+                    0
+                };
+                self.buffer[i][j] = self.buffer[i][j] & !planes | pixel;
             }
+        }
+    }
 
-            if i < n {
-                src.fill(false);
+    /// Set the first plane from a bitmap with a bit per pixel, row by row and most significant bit
+    /// first, like the COSMAC VIP keeps its display in memory
+    pub fn set_bitmap(&mut self, bitmap: &[u8]) {
+        for (i, row) in self.buffer.iter_mut().enumerate() {
+            for (j, pixel) in row.iter_mut().enumerate() {
+                let index = i * W + j;
+                let bit = bitmap[index / 8] >> (7 - index % 8) & 1;
+
+                if *pixel & 1 != bit {
+                    *pixel ^= 1;
+                    self.dirty = true;
+                }
             }
         }
     }
+
+    /// The first plane as a bitmap, see [`DisplayBuffer::set_bitmap`]
+    pub fn to_bitmap(&self) -> Vec<u8> {
+        let mut bitmap = vec![0; W * H / 8];
+
+        for (index, pixel) in self.buffer.iter().flatten().enumerate() {
+            bitmap[index / 8] |= (pixel & 1) << (7 - index % 8);
+        }
+
+        bitmap
+    }
 }
 
 impl KeypadState {
+    /// Start a new frame of input, a key that's released after this is reported by
+    /// [`KeypadState::pressed_key`] until the next frame
+    pub fn advance(&mut self) {
+        self.last_state.copy_from_slice(&self.state);
+    }
+
+    /// The keys that are held as a bitmask, bit `n` is set if key `n` is held
+    pub fn held(&self) -> u16 {
+        (0..super::KEY_COUNT)
+            .filter(|&i| self.pressed(i))
+            .fold(0, |mask, i| mask | 1 << i)
+    }
+
     pub fn new() -> Self {
         Self {
             state: [KeyState::Released; super::KEY_COUNT],
@@ -253,9 +337,16 @@ impl KeypadState {
         }
     }
 
+    /// Hold a key, keys past F are ignored
+    #[inline]
+    pub fn press(&mut self, key: usize) {
+        self.set(key, KeyState::Held);
+    }
+
+    /// Whether a key is held, keys past F never are
     #[inline]
     pub fn pressed(&self, key: usize) -> bool {
-        self.state[key] == KeyState::Held
+        self.state.get(key) == Some(&KeyState::Held)
     }
 
     pub fn pressed_key(&self) -> Option<usize> {
@@ -263,16 +354,50 @@ impl KeypadState {
             .find(|&i| self.last_state[i] == KeyState::Held && self.state[i] == KeyState::Released)
     }
 
-    pub fn update(&mut self, input: &egui::InputState) {
-        self.last_state.copy_from_slice(&self.state);
+    /// Release a key, keys past F are ignored
+    #[inline]
+    pub fn release(&mut self, key: usize) {
+        self.set(key, KeyState::Released);
+    }
 
-        for i in 0..super::KEY_COUNT {
-            if input.key_down(defaults::KEY_MAP[i]) {
-                self.state[i] = KeyState::Held;
-                continue;
-            }
+    fn set(&mut self, key: usize, key_state: KeyState) {
+        if let Some(state) = self.state.get_mut(key) {
+            *state = key_state;
+        }
+    }
 
-            self.state[i] = KeyState::Released;
+    /// Hold exactly the keys of a bitmask made by [`KeypadState::held`], every bit is a key so none
+    /// are out of range
+    pub fn set_held(&mut self, mask: u16) {
+        for (i, key_state) in self.state.iter_mut().enumerate() {
+            *key_state = match mask >> i & 1 {
+                0 => KeyState::Released,
+                _ => KeyState::Held,
+            };
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::KEY_COUNT;
+
+    #[test]
+    fn out_of_range_keys() {
+        let mut keypad_state = KeypadState::new();
+
+        keypad_state.press(0xF);
+        keypad_state.press(KEY_COUNT);
+        keypad_state.press(usize::MAX);
+        assert_eq!(keypad_state.held(), 0x8000);
+        assert!(!keypad_state.pressed(KEY_COUNT));
+
+        keypad_state.release(KEY_COUNT);
+        assert_eq!(keypad_state.held(), 0x8000);
+
+        keypad_state.advance();
+        keypad_state.release(0xF);
+        assert_eq!(keypad_state.pressed_key(), Some(0xF));
+    }
+}
@@ -0,0 +1,148 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::ControlFlow;
+
+use super::disassembler;
+use super::interfaces;
+use super::state;
+use super::xochip;
+use super::BackendError;
+use super::Options;
+use super::Timers;
+
+/// A read-only view of the registers, stack and memory, for debugging
+pub struct CpuState<'a> {
+    pub address: usize,
+    pub general: &'a [u8],
+    /// Address of the next instruction
+    pub index: usize,
+    pub memory: &'a [u8],
+    pub stack: &'a [u16],
+    pub timers: &'a Timers,
+}
+
+/// An emulated CHIP-8 variant
+///
+/// The trait is object safe, every variant is driven through a `Box<dyn Machine>` by the frontend
+pub trait Machine {
+    /// The XO-CHIP audio pattern buffer and pitch register, if the variant has them
+    fn audio(&self) -> Option<&xochip::Audio> {
+        None
+    }
+
+    /// Decrement the timers at the start of a tick
+    fn begin_tick(&mut self) {
+        self.timers_mut().decrement();
+    }
+
+    fn cpu_state(&self) -> CpuState<'_>;
+
+    /// Iterate over the pixels of the display row by row, marking the display as clean
+    ///
+    /// Every pixel is a bitmask of the planes that are lit
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError>;
+
+    fn display_buffer_aspect_ratio(&self) -> f32 {
+        let [width, height] = self.display_buffer_size();
+        width as f32 / height as f32
+    }
+
+    fn display_buffer_size(&self) -> [usize; 2];
+
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions>;
+
+    fn has_program_exited(&self) -> bool {
+        false
+    }
+
+    fn instruction_set(&self) -> disassembler::InstructionSet {
+        disassembler::InstructionSet::Chip8
+    }
+
+    fn is_display_buffer_dirty(&self) -> bool;
+
+    /// Whether the current tick is over after executing `executed` instructions out of the `n`
+    /// instructions per tick, variants that emulate the timing of real hardware ignore `n`
+    fn is_tick_over(&self, executed: u16, n: u16) -> bool {
+        executed >= n
+    }
+
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError>;
+
+    fn options_mut(&mut self) -> &mut Options;
+
+    /// Number of bitplanes the display has
+    fn plane_count(&self) -> usize {
+        1
+    }
+
+    /// Reset the machine to the state it was in right after loading the program
+    fn reset(&mut self);
+
+    /// Restore a snapshot taken by [`Machine::save_state`]
+    ///
+    /// The machine is left as it was if the snapshot is invalid
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError>;
+
+    /// Snapshot everything that changes while the program runs
+    fn save_state(&self, writer: &mut state::Writer);
+
+    /// Seed of the random number generator, which is part of the saved state
+    fn seed(&self) -> u64;
+
+    /// Start the random number generator over with `seed`, so that runs with the same input and seed
+    /// are identical
+    fn set_seed(&mut self, seed: u64);
+
+    /// Execute a single instruction without touching the timers
+    ///
+    /// Breaks if the instruction ends the tick early (drawing, waiting for a key or exiting)
+    fn step(
+        &mut self,
+        keypad_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError>;
+
+    /// Whether the variant has scrolling instructions
+    fn supports_scrolling(&self) -> bool {
+        false
+    }
+
+    /// Decrement the timers and execute at most `n` instructions
+    fn tick(
+        &mut self,
+        n: u16,
+        keypad_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<(), BackendError> {
+        self.begin_tick();
+
+        let mut executed = 0;
+        while !self.is_tick_over(executed, n) {
+            if self.step(keypad_state, persistent_storage)?.is_break() {
+                break;
+            }
+
+            executed += 1;
+        }
+
+        Ok(())
+    }
+
+    fn timers(&self) -> &Timers;
+
+    fn timers_mut(&mut self) -> &mut Timers;
+}
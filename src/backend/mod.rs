@@ -13,147 +13,135 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod assembler;
+mod cdp1802;
+pub mod chip48;
 pub mod chip8;
+mod defaults;
+pub mod disassembler;
 mod error;
+pub mod hires;
 mod instruction;
 pub mod interfaces;
+mod machine;
+mod quirks;
+pub mod random;
+pub mod state;
 pub mod superchip;
+pub mod xochip;
 
 pub use error::{BackendError, BackendErrorKind};
 pub use instruction::Instruction;
+pub use machine::{CpuState, Machine};
+pub use quirks::Quirks;
 
 pub use chip8::FONT_SIZE as MIN_FONT_SIZE;
+pub use chip8::MEMORY_PADDING as PROGRAM_ADDRESS;
 pub use superchip::FONT_SIZE as MAX_FONT_SIZE;
-pub use superchip::PERSISTENT_STORAGE_SIZE;
+pub use xochip::PERSISTENT_STORAGE_SIZE;
 
+pub const DEFAULT_INSTRUCTIONS_PER_TICK: u16 = 28;
 pub const KEY_COUNT: usize = 16; // 0-F
-
-pub enum Backend {
-    Chip8(chip8::Backend),
-    SuperChip(superchip::Backend),
-}
+pub const TICKS_PER_SECOND: usize = 60;
+
+pub const VARIANTS: [Variant; 8] = [
+    Variant {
+        id: "chip8",
+        name: "CHIP-8",
+        description: "The original CHIP-8 interpreter",
+        new: || Box::<chip8::Backend>::default(),
+    },
+    Variant {
+        id: "vip",
+        name: "CHIP-8 (COSMAC VIP)",
+        description: "The original CHIP-8 interpreter running as fast as it did on the COSMAC VIP, instructions take as long as they did on its 1802 CPU",
+        new: || Box::new(chip8::Backend::vip()),
+    },
+    Variant {
+        id: "hires",
+        name: "HIRES CHIP-8",
+        description: "CHIP-8 with a 64x64 display, picked for programs that start with its 1260 header",
+        new: || Box::<hires::Backend>::default(),
+    },
+    Variant {
+        id: "chip48",
+        name: "CHIP-48",
+        description: "CHIP-8 as reimplemented for the HP 48 calculators, BNNN jumps to NNN plus vX, shifts work on vX alone and SAVE and LOAD increment the address register by X",
+        new: || Box::<chip48::Backend>::default(),
+    },
+    Variant {
+        id: "schip1.0",
+        name: "SUPER-CHIP 1.0",
+        description: "The first release of SUPER-CHIP, big digits are pointed to by FX29 with the digit plus 0x10",
+        new: || Box::new(superchip::Backend::with_revision(superchip::Revision::V1_0)),
+    },
+    Variant {
+        id: "schip",
+        name: "SUPER-CHIP 1.1",
+        description: "A backwards-compatible extended version of CHIP-8 with higher resolution mode and new instructions",
+        new: || Box::<superchip::Backend>::default(),
+    },
+    Variant {
+        id: "schip-modern",
+        name: "SUPER-CHIP (Modern)",
+        description: "SUPER-CHIP as modern interpreters like Octo implement it, with 16x16 sprites in low resolution mode and the display cleared when switching modes",
+        new: || Box::new(superchip::Backend::with_revision(superchip::Revision::Modern)),
+    },
+    Variant {
+        id: "xochip",
+        name: "XO-CHIP",
+        description: "A modern extension of SUPER-CHIP with 64 KiB of memory, four colors and programmable audio",
+        new: || Box::<xochip::Backend>::default(),
+    },
+];
+
+pub type Backend = Box<dyn Machine>;
 
 pub struct Options {
     pub copy_and_shift: bool,
+    /// Wait for the next tick after drawing a sprite, like the COSMAC VIP waits for the display
+    /// interrupt
+    pub display_wait: bool,
     pub increment_address: bool,
+    /// Run 0NNN as a call to a machine code subroutine of the 1802, for the hybrid programs of the
+    /// COSMAC VIP
+    pub machine_code: bool,
     pub quirky_jump: bool,
     pub reset_flag: bool,
 }
 
+#[derive(Default)]
 pub struct Timers {
     delay: u8,
     pub sound: u8,
 }
 
-impl Backend {
-    pub fn get_display_buffer<'a>(
-        &'a mut self,
-    ) -> Result<impl Iterator<Item = bool> + 'a, BackendError> {
-        match self {
-            Self::Chip8(backend) => backend
-                .display_buffer
-                .as_mut()
-                .and_then(|buffer| Some(buffer.get_flattened()))
-                .ok_or(BackendError {
-                    kind: BackendErrorKind::DisplayNotConnected,
-                    instruction: None,
-                }),
-            Self::SuperChip(backend) => Ok(backend.display_buffer.get_flattened()),
-        }
-    }
-
-    pub fn display_buffer_aspect_ratio(&self) -> f32 {
-        match self {
-            Self::Chip8(..) => chip8::DISPLAY_BUFFER_ASPECT_RATIO,
-            Self::SuperChip(..) => superchip::DISPLAY_BUFFER_ASPECT_RATIO,
-        }
-    }
-
-    pub fn display_buffer_size(&self) -> [usize; 2] {
-        match self {
-            Self::Chip8(..) => [chip8::DISPLAY_BUFFER_WIDTH, chip8::DISPLAY_BUFFER_HEIGHT],
-            Self::SuperChip(..) => [
-                superchip::DISPLAY_BUFFER_WIDTH,
-                superchip::DISPLAY_BUFFER_HEIGHT,
-            ],
-        }
-    }
-
-    pub fn get_display_options_mut(&mut self) -> &mut interfaces::DisplayOptions {
-        match self {
-            Self::Chip8(backend) => &mut backend.display_buffer.as_mut().unwrap().options,
-            Self::SuperChip(backend) => &mut backend.display_buffer.options,
-        }
-    }
-
-    pub fn is_display_buffer_dirty(&mut self) -> bool {
-        match self {
-            Self::Chip8(backend) => backend.display_buffer.as_mut().unwrap().is_dirty(),
-            Self::SuperChip(backend) => backend.display_buffer.is_dirty(),
-        }
-    }
-
-    pub fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
-        match self {
-            Self::Chip8(backend) => backend.load(font, program),
-            Self::SuperChip(backend) => backend.load(font, program),
-        }
-    }
-
-    pub fn get_options_mut(&mut self) -> &mut Options {
-        match self {
-            Self::Chip8(backend) => &mut backend.options,
-            Self::SuperChip(backend) => backend.options_mut(),
-        }
-    }
-
-    pub fn has_program_exited(&self) -> bool {
-        match self {
-            Self::Chip8(..) => false,
-            Self::SuperChip(backend) => backend.program_exited,
-        }
-    }
+#[derive(Clone, Copy)]
+pub struct Variant {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub new: fn() -> Backend,
+}
 
-    pub fn reset(&mut self) {
-        match self {
-            Self::Chip8(backend) => {
-                backend.reset();
-                backend.display_buffer.as_mut().unwrap().clear();
-            }
-            Self::SuperChip(backend) => {
-                backend.reset();
-                backend.display_buffer.clear();
-            }
-        }
+impl Timers {
+    pub fn decrement(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
     }
 
-    pub fn tick(
-        &mut self,
-        n: u8,
-        keyboard_state: &mut interfaces::KeypadState,
-        persistent_storage: Option<&mut [u8]>,
-    ) -> Result<(), BackendError> {
-        match self {
-            Self::Chip8(backend) => backend.tick(n, keyboard_state),
-            Self::SuperChip(backend) => backend.tick(
-                n,
-                keyboard_state,
-                persistent_storage
-                    .expect("persistent_storage shouldn't be None while using SuperChip backend"),
-            ),
-        }
+    #[inline]
+    pub fn delay(&self) -> u8 {
+        self.delay
     }
 
-    pub fn get_timers(&self) -> &Timers {
-        match self {
-            Self::Chip8(backend) => &backend.timers,
-            Self::SuperChip(backend) => backend.timers(),
-        }
+    /// Timers with the given values, for variants implemented outside of this crate
+    pub fn new(delay: u8, sound: u8) -> Self {
+        Self { delay, sound }
     }
 }
 
-impl Default for Backend {
-    fn default() -> Self {
-        Self::Chip8(Default::default())
-    }
+/// Identifier of the variant a CHIP-8 program is meant for, if it can be told from the program
+pub fn detect_variant(program: &[u8]) -> Option<&'static str> {
+    hires::has_header(program).then_some("hires")
 }
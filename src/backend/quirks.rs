@@ -0,0 +1,102 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::Machine;
+
+/// Quirks to override a backend's defaults with, the ones that aren't given are left alone
+#[derive(Clone, Default)]
+pub struct Quirks {
+    pub clip_sprites: Option<bool>,
+    pub copy_and_shift: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub half_pixel_scrolling: Option<bool>,
+    pub increment_address: Option<bool>,
+    pub machine_code: Option<bool>,
+    pub quirky_jump: Option<bool>,
+    pub reset_flag: Option<bool>,
+}
+
+impl Quirks {
+    /// Override the backend's defaults with the quirks that were given
+    pub fn apply(&self, backend: &mut dyn Machine) {
+        let options = backend.options_mut();
+
+        for (quirk, value) in [
+            (&mut options.copy_and_shift, self.copy_and_shift),
+            (&mut options.display_wait, self.display_wait),
+            (&mut options.increment_address, self.increment_address),
+            (&mut options.machine_code, self.machine_code),
+            (&mut options.quirky_jump, self.quirky_jump),
+            (&mut options.reset_flag, self.reset_flag),
+        ] {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+
+        let Some(display_options) = backend.display_options_mut() else {
+            return;
+        };
+
+        for (quirk, value) in [
+            (&mut display_options.clip_sprites, self.clip_sprites),
+            (
+                &mut display_options.half_pixel_scrolling,
+                self.half_pixel_scrolling,
+            ),
+        ] {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+
+    /// Every quirk of the backend as it's currently set
+    pub fn capture(backend: &mut dyn Machine) -> Self {
+        let options = backend.options_mut();
+
+        let mut quirks = Self {
+            copy_and_shift: Some(options.copy_and_shift),
+            display_wait: Some(options.display_wait),
+            increment_address: Some(options.increment_address),
+            machine_code: Some(options.machine_code),
+            quirky_jump: Some(options.quirky_jump),
+            reset_flag: Some(options.reset_flag),
+            ..Default::default()
+        };
+
+        if let Some(display_options) = backend.display_options_mut() {
+            quirks.clip_sprites = Some(display_options.clip_sprites);
+            quirks.half_pixel_scrolling = Some(display_options.half_pixel_scrolling);
+        }
+
+        quirks
+    }
+
+    /// Get a quirk by its command-line and config file name
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Option<bool>> {
+        match name {
+            "clip-sprites" => Some(&mut self.clip_sprites),
+            "copy-and-shift" => Some(&mut self.copy_and_shift),
+            "display-wait" => Some(&mut self.display_wait),
+            "half-pixel-scrolling" => Some(&mut self.half_pixel_scrolling),
+            "increment-address" => Some(&mut self.increment_address),
+            "machine-code" => Some(&mut self.machine_code),
+            "quirky-jump" => Some(&mut self.quirky_jump),
+            "reset-flag" => Some(&mut self.reset_flag),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::hash_map;
+use std::hash::{BuildHasher, Hasher};
+
+use super::state;
+use super::BackendError;
+
+const INCREMENT: u64 = 0x9E3779B97F4A7C15;
+
+/// A SplitMix64 generator, every seed gives the same sequence on every platform
+pub struct Random {
+    seed: u64,
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Generate the next byte of the sequence
+    pub fn next_u8(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(INCREMENT);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+
+    /// Start the sequence of the seed over
+    #[inline]
+    pub fn reset(&mut self) {
+        self.state = self.seed;
+    }
+
+    pub fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        self.seed = reader.read_u64()?;
+        self.state = reader.read_u64()?;
+
+        Ok(())
+    }
+
+    pub fn save_state(&self, writer: &mut state::Writer) {
+        writer.write_u64(self.seed);
+        writer.write_u64(self.state);
+    }
+
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(new_seed())
+    }
+}
+
+/// Pick an unpredictable seed, from the randomness std uses for hash maps
+pub fn new_seed() -> u64 {
+    hash_map::RandomState::new().build_hasher().finish()
+}
@@ -0,0 +1,214 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::BackendError;
+use super::BackendErrorKind;
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+#[derive(Default)]
+#[repr(transparent)]
+pub struct Writer {
+    data: Vec<u8>,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, BackendError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Read a length prefixed byte slice
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], BackendError> {
+        let length = self.read_u32()? as usize;
+        self.read_exact(length)
+    }
+
+    pub fn read_exact(&mut self, length: usize) -> Result<&'a [u8], BackendError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(invalid)?;
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, BackendError> {
+        Ok(u16::from_le_bytes(self.read_exact(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, BackendError> {
+        Ok(u32::from_le_bytes(self.read_exact(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, BackendError> {
+        Ok(u64::from_le_bytes(self.read_exact(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, BackendError> {
+        Ok(self.read_exact(1)?[0])
+    }
+
+    /// Number of bytes that are left
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+impl Writer {
+    #[inline]
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline]
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    /// Write a length prefixed byte slice
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write_exact(bytes);
+    }
+
+    #[inline]
+    pub fn write_exact(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+}
+
+pub fn invalid() -> BackendError {
+    BackendError {
+        instruction: None,
+        kind: BackendErrorKind::StateInvalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{self, interfaces, Machine};
+
+    /// Draws random sprites and calls a subroutine in a loop, to change most of the machine
+    const PROGRAM: [u8; 20] = [
+        0x60, 0x12, 0xF0, 0x15, 0xC1, 0xFF, 0xA2, 0x00, 0xD0, 0x15, 0x70, 0x01, 0x22, 0x12, 0x12,
+        0x04, 0x00, 0x00, 0x00, 0xEE,
+    ];
+
+    fn run(backend: &mut dyn Machine, ticks: usize) {
+        let mut persistent_storage = [0; backend::PERSISTENT_STORAGE_SIZE];
+
+        for _ in 0..ticks {
+            backend
+                .tick(
+                    7,
+                    &mut interfaces::KeypadState::new(),
+                    &mut persistent_storage,
+                )
+                .unwrap();
+        }
+    }
+
+    fn snapshot(backend: &dyn Machine) -> Vec<u8> {
+        let mut writer = Writer::new();
+        backend.save_state(&mut writer);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn round_trip() {
+        for variant in backend::VARIANTS {
+            let mut backend = (variant.new)();
+            backend.set_seed(1);
+            backend.load(None, &PROGRAM).unwrap();
+            run(backend.as_mut(), 3);
+
+            let saved = snapshot(backend.as_ref());
+            run(backend.as_mut(), 5);
+            assert_ne!(snapshot(backend.as_ref()), saved, "{}", variant.id);
+
+            let mut reader = Reader::new(&saved);
+            backend.restore_state(&mut reader).unwrap();
+            assert!(reader.is_empty(), "{}", variant.id);
+            assert_eq!(snapshot(backend.as_ref()), saved, "{}", variant.id);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        for variant in backend::VARIANTS {
+            let mut backend = (variant.new)();
+            backend.load(None, &PROGRAM).unwrap();
+            run(backend.as_mut(), 3);
+
+            let saved = snapshot(backend.as_ref());
+            run(backend.as_mut(), 5);
+            let current = snapshot(backend.as_ref());
+
+            // the end of a snapshot is read after the display has already been restored
+            for length in [0, 1, saved.len() / 2, saved.len() - 9, saved.len() - 1] {
+                let error = backend
+                    .restore_state(&mut Reader::new(&saved[..length]))
+                    .unwrap_err();
+
+                assert!(matches!(error.kind, BackendErrorKind::StateInvalid));
+                assert_eq!(snapshot(backend.as_ref()), current, "{}", variant.id);
+            }
+        }
+    }
+}
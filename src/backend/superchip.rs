@@ -13,16 +13,18 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::mem;
 use std::ops::ControlFlow;
 
-use crate::defaults;
-
 use super::chip8;
+use super::defaults;
+use super::disassembler;
 use super::interfaces;
+use super::state;
 use super::BackendError;
 use super::BackendErrorKind;
+use super::CpuState;
 use super::Instruction;
+use super::Machine;
 
 pub const DISPLAY_BUFFER_ASPECT_RATIO: f32 = (DISPLAY_BUFFER_WIDTH / DISPLAY_BUFFER_HEIGHT) as f32;
 pub const DISPLAY_BUFFER_HEIGHT: usize = 64;
@@ -30,15 +32,29 @@ pub const DISPLAY_BUFFER_WIDTH: usize = 128;
 pub const FONT_SIZE: usize = chip8::FONT_SIZE + HIRES_FONT_SIZE;
 pub const PERSISTENT_STORAGE_SIZE: usize = 8;
 
-const HIRES_CHARACTER_COUNT: usize = 10; // 0-9
-const HIRES_CHARACTER_SIZE: usize = 10;
-const HIRES_FONT_SIZE: usize = HIRES_CHARACTER_SIZE * HIRES_CHARACTER_COUNT;
+pub(super) const HIRES_CHARACTER_COUNT: usize = 10; // 0-9
+pub(super) const HIRES_CHARACTER_SIZE: usize = 10;
+pub(super) const HIRES_FONT_SIZE: usize = HIRES_CHARACTER_SIZE * HIRES_CHARACTER_COUNT;
 
 pub struct Backend {
     pub(super) display_buffer:
         interfaces::DisplayBuffer<DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT>,
     inner: chip8::Backend,
     pub(super) program_exited: bool,
+    revision: Revision,
+}
+
+/// Release of SUPER-CHIP whose behaviour is emulated
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Revision {
+    /// Big digits are pointed to by FX29 with the digit plus 0x10, there's no FX30 and no
+    /// scrolling
+    V1_0,
+    /// Big digits are pointed to by FX30 and the display can be scrolled
+    V1_1,
+    /// SUPER-CHIP as modern interpreters like Octo implement it, sprites are 16x16 in low
+    /// resolution mode, switching modes clears the display and collisions aren't counted by row
+    Modern,
 }
 
 impl Backend {
@@ -50,37 +66,45 @@ impl Backend {
         persistent_storage: &mut [u8],
     ) -> Result<ControlFlow<()>, BackendError> {
         match instruction.operator_code() {
-            0x0 if instruction.operand_xy() == 0x0B => self
+            // scrolling came with 1.1, 1.0 runs them as calls to machine code like CHIP-8
+            0x0 if instruction.operand_xy() == 0x0B && self.revision != Revision::V1_0 => self
                 .display_buffer
                 .scroll_up(instruction.operand_n() as usize),
-            0x0 if instruction.operand_xy() == 0x0C => self
+            0x0 if instruction.operand_xy() == 0x0C && self.revision != Revision::V1_0 => self
                 .display_buffer
                 .scroll_down(instruction.operand_n() as usize),
 
             0x0 if instruction.operand_nnn() == 0x0E0 => self.display_buffer.clear(),
 
-            0x0 if instruction.operand_nnn() == 0x0FB => self.display_buffer.scroll_right(4),
-            0x0 if instruction.operand_nnn() == 0x0FC => self.display_buffer.scroll_left(4),
+            0x0 if instruction.operand_nnn() == 0x0FB && self.revision != Revision::V1_0 => {
+                self.display_buffer.scroll_right(4)
+            }
+            0x0 if instruction.operand_nnn() == 0x0FC && self.revision != Revision::V1_0 => {
+                self.display_buffer.scroll_left(4)
+            }
 
             0x0 if instruction.operand_nnn() == 0x0FD => {
                 self.program_exited = true;
                 return Ok(ControlFlow::Break(()));
             }
 
-            0x0 if instruction.operand_nnn() == 0x0FE => {
-                self.display_buffer.half_resolution = true;
-                self.display_buffer.clear();
-            }
-            0x0 if instruction.operand_nnn() == 0x0FF => {
-                self.display_buffer.half_resolution = false;
-                self.display_buffer.clear();
+            0x0 if matches!(instruction.operand_nnn(), 0x0FE | 0x0FF) => {
+                self.display_buffer.half_resolution = instruction.operand_nnn() == 0x0FE;
+
+                if self.revision == Revision::Modern {
+                    self.display_buffer.clear();
+                }
             }
 
             0xD => {
-                let n = if instruction.operand_n() == 0 {
-                    32
-                } else {
-                    instruction.operand_n() as usize
+                let legacy_low_resolution =
+                    self.display_buffer.half_resolution && self.revision != Revision::Modern;
+
+                // the legacy revisions draw 8x16 sprites in low resolution mode
+                let n = match instruction.operand_n() {
+                    0 if legacy_low_resolution => 16,
+                    0 => 32,
+                    n => n as usize,
                 };
 
                 if self.inner.registers.address + n >= self.inner.memory.len() {
@@ -99,14 +123,22 @@ impl Backend {
                         [self.inner.registers.address..self.inner.registers.address + n],
                 );
 
-                self.inner.registers.general[15] = if self.display_buffer.half_resolution {
-                    (colliding_rows > 0) as u8
-                } else {
-                    colliding_rows as u8
+                self.inner.registers.general[15] =
+                    if self.display_buffer.half_resolution || self.revision == Revision::Modern {
+                        (colliding_rows > 0) as u8
+                    } else {
+                        colliding_rows as u8
+                    };
+
+                // the legacy revisions only wait for the display in low resolution mode
+                if self.inner.options.display_wait
+                    && (legacy_low_resolution || self.revision == Revision::Modern)
+                {
+                    return Ok(ControlFlow::Break(()));
                 }
             }
 
-            0xF if instruction.operand_nn() == 0x29 => 'block: {
+            0xF if instruction.operand_nn() == 0x29 && self.revision == Revision::V1_0 => 'block: {
                 let character_code = self.inner.registers.general[instruction.operand_x()] as usize;
 
                 if character_code < super::KEY_COUNT {
@@ -125,7 +157,7 @@ impl Backend {
                     chip8::FONT_SIZE + (character_code & 0xF) * HIRES_CHARACTER_SIZE
             }
 
-            0xF if instruction.operand_nn() == 0x30 => {
+            0xF if instruction.operand_nn() == 0x30 && self.revision != Revision::V1_0 => {
                 let character_code = self.inner.registers.general[instruction.operand_x()] as usize;
 
                 if character_code >= HIRES_CHARACTER_COUNT {
@@ -142,12 +174,12 @@ impl Backend {
             0xF if instruction.operand_nn() == 0x75 => {
                 let registers = &self.inner.registers.general
                     [..=instruction.operand_x().min(PERSISTENT_STORAGE_SIZE - 1)];
-                persistent_storage.copy_from_slice(registers);
+                persistent_storage[..registers.len()].copy_from_slice(registers);
             }
             0xF if instruction.operand_nn() == 0x85 => {
                 let registers = &mut self.inner.registers.general
                     [..=instruction.operand_x().min(PERSISTENT_STORAGE_SIZE - 1)];
-                registers.copy_from_slice(&persistent_storage[..PERSISTENT_STORAGE_SIZE]);
+                registers.copy_from_slice(&persistent_storage[..registers.len()]);
             }
 
             _ => return self.inner.execute(index, instruction, keyboard_state),
@@ -156,22 +188,11 @@ impl Backend {
         Ok(ControlFlow::Continue(()))
     }
 
-    pub fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), super::BackendError> {
-        let font = font.unwrap_or(&defaults::BACKEND_FONT);
-
-        self.inner.load(Some(&font[..chip8::FONT_SIZE]), program)?;
-
-        self.inner.memory[chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE].copy_from_slice(
-            font.get(chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE)
-                .unwrap_or(
-                    &defaults::BACKEND_FONT[chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE],
-                ),
-        );
-
-        Ok(())
-    }
-
-    pub fn new(options: super::Options, display_options: interfaces::DisplayOptions) -> Self {
+    pub fn new(
+        options: super::Options,
+        display_options: interfaces::DisplayOptions,
+        revision: Revision,
+    ) -> Self {
         let mut display_buffer = interfaces::DisplayBuffer::new(display_options);
         display_buffer.half_resolution = true;
 
@@ -179,80 +200,264 @@ impl Backend {
             display_buffer,
             inner: chip8::Backend::new(options, None),
             program_exited: false,
+            revision,
         }
     }
 
+    /// A backend with the default quirks of `revision`
+    pub fn with_revision(revision: Revision) -> Self {
+        let legacy = revision != Revision::Modern;
+
+        Self::new(
+            super::Options {
+                copy_and_shift: false,
+                display_wait: legacy,
+                increment_address: false,
+                machine_code: false,
+                quirky_jump: true,
+                reset_flag: false,
+            },
+            interfaces::DisplayOptions {
+                clip_sprites: true,
+                half_pixel_scrolling: legacy,
+            },
+            revision,
+        )
+    }
+}
+
+impl Machine for Backend {
+    #[inline]
+    fn cpu_state(&self) -> CpuState<'_> {
+        self.inner.cpu_state()
+    }
+
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError> {
+        Ok(Box::new(self.display_buffer.get_flattened()))
+    }
+
+    fn display_buffer_size(&self) -> [usize; 2] {
+        [DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT]
+    }
+
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions> {
+        Some(&mut self.display_buffer.options)
+    }
+
     #[inline]
-    pub fn options_mut(&mut self) -> &mut super::Options {
+    fn has_program_exited(&self) -> bool {
+        self.program_exited
+    }
+
+    #[inline]
+    fn instruction_set(&self) -> disassembler::InstructionSet {
+        disassembler::InstructionSet::SuperChip
+    }
+
+    #[inline]
+    fn is_display_buffer_dirty(&self) -> bool {
+        self.display_buffer.is_dirty()
+    }
+
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
+        let font = font.unwrap_or(&defaults::FONT);
+
+        self.inner.load(Some(&font[..chip8::FONT_SIZE]), program)?;
+
+        self.inner.memory[chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE].copy_from_slice(
+            font.get(chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE)
+                .unwrap_or(&defaults::FONT[chip8::FONT_SIZE..chip8::FONT_SIZE + HIRES_FONT_SIZE]),
+        );
+
+        Ok(())
+    }
+
+    #[inline]
+    fn options_mut(&mut self) -> &mut super::Options {
         &mut self.inner.options
     }
 
-    pub fn reset(&mut self) {
+    fn reset(&mut self) {
         self.program_exited = false;
         self.inner.reset();
+        self.display_buffer.clear();
     }
 
-    pub fn tick(
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        let program_exited = reader.read_bool()?;
+
+        self.inner
+            .restore_state_then(reader, |reader| self.display_buffer.restore_state(reader))?;
+
+        self.program_exited = program_exited;
+
+        Ok(())
+    }
+
+    fn save_state(&self, writer: &mut state::Writer) {
+        writer.write_bool(self.program_exited);
+
+        self.inner.save_state(writer);
+        self.display_buffer.save_state(writer);
+    }
+
+    #[inline]
+    fn seed(&self) -> u64 {
+        self.inner.seed()
+    }
+
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn step(
         &mut self,
-        n: u8,
         keyboard_state: &mut interfaces::KeypadState,
         persistent_storage: &mut [u8],
-    ) -> Result<(), BackendError> {
-        if !self.inner.loaded {
-            return Err(BackendError {
-                instruction: None,
-                kind: BackendErrorKind::ProgramNotLoaded,
-            });
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let (index, instruction) = self.inner.fetch()?;
+        self.execute(index, instruction, keyboard_state, persistent_storage)
+    }
+
+    #[inline]
+    fn supports_scrolling(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn timers(&self) -> &super::Timers {
+        &self.inner.timers
+    }
+
+    #[inline]
+    fn timers_mut(&mut self) -> &mut super::Timers {
+        &mut self.inner.timers
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::with_revision(Revision::V1_1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REVISIONS: [Revision; 3] = [Revision::V1_0, Revision::V1_1, Revision::Modern];
+    /// Where [`program`] puts the sprite, 32 bytes of set pixels
+    const SPRITE_ADDRESS: usize = 0x300;
+
+    /// The instructions followed by the sprite
+    fn program(instructions: &[u16]) -> Vec<u8> {
+        let mut program = vec![0; SPRITE_ADDRESS - chip8::MEMORY_PADDING + 32];
+
+        for (i, instruction) in instructions.iter().enumerate() {
+            program[i * 2..i * 2 + 2].copy_from_slice(&instruction.to_be_bytes());
         }
+        program[SPRITE_ADDRESS - chip8::MEMORY_PADDING..].fill(0xFF);
 
-        self.inner.timers.delay = self.inner.timers.delay.saturating_sub(1);
-        self.inner.timers.sound = self.inner.timers.sound.saturating_sub(1);
+        program
+    }
 
-        for _ in 0..n {
-            if self.inner.index + 1 >= self.inner.memory.len() {
-                return Err(BackendError {
-                    instruction: Some((self.inner.index, None)),
-                    kind: BackendErrorKind::MemoryOverflow,
-                });
-            }
+    /// Run every instruction of the program
+    fn run(revision: Revision, instructions: &[u16]) -> Result<Backend, BackendError> {
+        let mut backend = Backend::with_revision(revision);
+        backend.load(None, &program(instructions))?;
+
+        for _ in instructions {
+            let _ = backend.step(
+                &mut interfaces::KeypadState::new(),
+                &mut [0; PERSISTENT_STORAGE_SIZE],
+            )?;
+        }
+
+        Ok(backend)
+    }
 
-            let instruction = Instruction::new([
-                self.inner.memory[self.inner.index],
-                self.inner.memory[self.inner.index + 1],
-            ]);
+    fn lit_pixels(backend: &mut Backend) -> usize {
+        backend
+            .display_buffer()
+            .unwrap()
+            .filter(|&pixel| pixel != 0)
+            .count()
+    }
 
-            let last_index = self.inner.index;
-            self.inner.index += mem::size_of::<Instruction>();
+    #[test]
+    fn big_sprites_in_low_resolution() {
+        for revision in REVISIONS {
+            let mut backend = run(revision, &[0xA300, 0xD000]).unwrap();
+
+            // every pixel is 2x2 in low resolution mode
+            let size = match revision {
+                Revision::Modern => 16 * 16,
+                _ => 8 * 16,
+            };
+            assert_eq!(lit_pixels(&mut backend), size * 4);
+        }
+    }
 
-            let control_flow =
-                self.execute(last_index, instruction, keyboard_state, persistent_storage)?;
+    #[test]
+    fn clear_on_mode_switch() {
+        for revision in REVISIONS {
+            for mode in [0x00FE, 0x00FF] {
+                let mut backend = run(revision, &[0x00FF, 0xA300, 0xD001, mode]).unwrap();
 
-            if control_flow.is_break() {
-                break;
+                let cleared = lit_pixels(&mut backend) == 0;
+                assert_eq!(cleared, revision == Revision::Modern, "{:04X}", mode);
             }
         }
+    }
 
-        Ok(())
+    #[test]
+    fn big_digits() {
+        let address = chip8::FONT_SIZE + 3 * HIRES_CHARACTER_SIZE;
+
+        let backend = run(Revision::V1_0, &[0x6013, 0xF029]).unwrap();
+        assert_eq!(backend.inner.registers.address, address);
+        let error = run(Revision::V1_0, &[0x6003, 0xF030]).err().unwrap();
+        assert!(matches!(
+            error.kind,
+            BackendErrorKind::UnrecognizedInstruction
+        ));
+
+        for revision in [Revision::V1_1, Revision::Modern] {
+            let backend = run(revision, &[0x6003, 0xF030]).unwrap();
+            assert_eq!(backend.inner.registers.address, address);
+            let error = run(revision, &[0x6013, 0xF029]).err().unwrap();
+            assert!(matches!(error.kind, BackendErrorKind::UnrecognizedSprite));
+        }
     }
 
-    pub fn timers(&self) -> &super::Timers {
-        &self.inner.timers
+    #[test]
+    fn collisions_by_row() {
+        for revision in REVISIONS {
+            let backend = run(revision, &[0x00FF, 0xA300, 0xD003, 0xD003]).unwrap();
+
+            let collisions = match revision {
+                Revision::Modern => 1,
+                _ => 3,
+            };
+            assert_eq!(backend.inner.registers.general[15], collisions);
+
+            // low resolution mode only tells whether there was a collision
+            let backend = run(revision, &[0xA300, 0xD003, 0xD003]).unwrap();
+            assert_eq!(backend.inner.registers.general[15], 1);
+        }
     }
-}
 
-impl Default for Backend {
-    fn default() -> Self {
-        Self::new(
-            super::Options {
-                copy_and_shift: false,
-                increment_address: false,
-                quirky_jump: true,
-                reset_flag: false,
-            },
-            interfaces::DisplayOptions {
-                clip_sprites: true,
-                half_pixel_scrolling: false,
-            },
-        )
+    #[test]
+    fn scrolling() {
+        for revision in REVISIONS {
+            for scroll in [0x00C1, 0x00FB, 0x00FC] {
+                let mut backend = run(revision, &[0x00FF, 0xA300, 0xD001, scroll]).unwrap();
+
+                let pixels: Vec<u8> = backend.display_buffer().unwrap().collect();
+                let scrolled = pixels[..8].contains(&0);
+                assert_eq!(scrolled, revision != Revision::V1_0, "{:04X}", scroll);
+            }
+        }
     }
 }
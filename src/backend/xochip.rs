@@ -0,0 +1,537 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::mem;
+use std::ops::ControlFlow;
+
+use super::chip8;
+use super::defaults;
+use super::disassembler;
+use super::interfaces;
+use super::state;
+use super::superchip;
+use super::BackendError;
+use super::BackendErrorKind;
+use super::CpuState;
+use super::Instruction;
+use super::Machine;
+
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+pub const DISPLAY_BUFFER_ASPECT_RATIO: f32 = (DISPLAY_BUFFER_WIDTH / DISPLAY_BUFFER_HEIGHT) as f32;
+pub const DISPLAY_BUFFER_HEIGHT: usize = 64;
+pub const DISPLAY_BUFFER_WIDTH: usize = 128;
+pub const PERSISTENT_STORAGE_SIZE: usize = 16;
+
+const MEMORY_SIZE: usize = 65536;
+const PLANE_COUNT: usize = 2;
+
+#[derive(Clone, Copy)]
+pub struct Audio {
+    pub pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+}
+
+pub struct Backend {
+    pub(super) audio: Audio,
+    pub(super) display_buffer:
+        interfaces::DisplayBuffer<DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT>,
+    inner: chip8::Backend,
+    pub(super) program_exited: bool,
+}
+
+impl Audio {
+    /// Playback rate of the pattern buffer in bits per second
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+impl Backend {
+    pub(super) fn execute(
+        &mut self,
+        index: usize,
+        instruction: Instruction,
+        keyboard_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError> {
+        match instruction.operator_code() {
+            0x0 if instruction.operand_xy() == 0x0C => self
+                .display_buffer
+                .scroll_down(instruction.operand_n() as usize),
+            0x0 if instruction.operand_xy() == 0x0D => self
+                .display_buffer
+                .scroll_up(instruction.operand_n() as usize),
+
+            0x0 if instruction.operand_nnn() == 0x0E0 => self.display_buffer.clear(),
+
+            0x0 if instruction.operand_nnn() == 0x0FB => self.display_buffer.scroll_right(4),
+            0x0 if instruction.operand_nnn() == 0x0FC => self.display_buffer.scroll_left(4),
+
+            0x0 if instruction.operand_nnn() == 0x0FD => {
+                self.program_exited = true;
+                return Ok(ControlFlow::Break(()));
+            }
+
+            0x0 if instruction.operand_nnn() == 0x0FE => {
+                self.display_buffer.half_resolution = true;
+                self.display_buffer.clear_planes(u8::MAX);
+            }
+            0x0 if instruction.operand_nnn() == 0x0FF => {
+                self.display_buffer.half_resolution = false;
+                self.display_buffer.clear_planes(u8::MAX);
+            }
+
+            0x5 if instruction.operand_n() == 0x2 || instruction.operand_n() == 0x3 => {
+                let x = instruction.operand_x();
+                let y = instruction.operand_y();
+                let count = x.abs_diff(y) + 1;
+
+                if self.inner.registers.address + count > self.inner.memory.len() {
+                    return Err(BackendError {
+                        instruction: Some((index, Some(instruction))),
+                        kind: BackendErrorKind::MemoryOverflow,
+                    });
+                }
+
+                for i in 0..count {
+                    let register = if x <= y { x + i } else { x - i };
+                    let address = self.inner.registers.address + i;
+
+                    match instruction.operand_n() {
+                        0x2 => self.inner.memory[address] = self.inner.registers.general[register],
+                        0x3 => self.inner.registers.general[register] = self.inner.memory[address],
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            0xD => {
+                let n = if instruction.operand_n() == 0 {
+                    32
+                } else {
+                    instruction.operand_n() as usize
+                };
+
+                let planes = self.display_buffer.planes;
+                let coordinates = (
+                    self.inner.registers.general[instruction.operand_x()] as usize,
+                    self.inner.registers.general[instruction.operand_y()] as usize,
+                );
+                let mut address = self.inner.registers.address;
+                let mut colliding_rows = 0;
+
+                for plane in (0..PLANE_COUNT).filter(|plane| planes & (1 << plane) != 0) {
+                    if address + n > self.inner.memory.len() {
+                        return Err(BackendError {
+                            instruction: Some((index, Some(instruction))),
+                            kind: BackendErrorKind::MemoryOverflow,
+                        });
+                    }
+
+                    colliding_rows += self.display_buffer.draw_plane(
+                        plane,
+                        coordinates,
+                        &self.inner.memory[address..address + n],
+                    );
+                    address += n;
+                }
+
+                self.inner.registers.general[15] = (colliding_rows > 0) as u8;
+
+                if self.inner.options.display_wait {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+
+            0xF if instruction.operand_nnn() == 0x000 => {
+                let next_index = self.inner.index;
+
+                if next_index + 1 >= self.inner.memory.len() {
+                    return Err(BackendError {
+                        instruction: Some((index, Some(instruction))),
+                        kind: BackendErrorKind::MemoryOverflow,
+                    });
+                }
+
+                self.inner.registers.address = u16::from_be_bytes([
+                    self.inner.memory[next_index],
+                    self.inner.memory[next_index + 1],
+                ]) as usize;
+                self.inner.index += mem::size_of::<Instruction>();
+            }
+
+            0xF if instruction.operand_nn() == 0x01 => {
+                self.display_buffer.planes = instruction.operand_x() as u8 & 0b11;
+            }
+
+            0xF if instruction.operand_nnn() == 0x002 => {
+                let address = self.inner.registers.address;
+
+                if address + AUDIO_PATTERN_SIZE > self.inner.memory.len() {
+                    return Err(BackendError {
+                        instruction: Some((index, Some(instruction))),
+                        kind: BackendErrorKind::MemoryOverflow,
+                    });
+                }
+
+                self.audio
+                    .pattern
+                    .copy_from_slice(&self.inner.memory[address..address + AUDIO_PATTERN_SIZE]);
+            }
+
+            0xF if instruction.operand_nn() == 0x30 => {
+                let character_code = self.inner.registers.general[instruction.operand_x()] as usize;
+
+                if character_code >= superchip::HIRES_CHARACTER_COUNT {
+                    return Err(BackendError {
+                        instruction: Some((index, Some(instruction))),
+                        kind: BackendErrorKind::UnrecognizedSprite,
+                    });
+                }
+
+                self.inner.registers.address =
+                    chip8::FONT_SIZE + character_code * superchip::HIRES_CHARACTER_SIZE
+            }
+
+            0xF if instruction.operand_nn() == 0x3A => {
+                self.audio.pitch = self.inner.registers.general[instruction.operand_x()];
+            }
+
+            0xF if instruction.operand_nn() == 0x75 => {
+                let registers = &self.inner.registers.general
+                    [..=instruction.operand_x().min(PERSISTENT_STORAGE_SIZE - 1)];
+                persistent_storage[..registers.len()].copy_from_slice(registers);
+            }
+            0xF if instruction.operand_nn() == 0x85 => {
+                let registers = &mut self.inner.registers.general
+                    [..=instruction.operand_x().min(PERSISTENT_STORAGE_SIZE - 1)];
+                registers.copy_from_slice(&persistent_storage[..registers.len()]);
+            }
+
+            0x3 | 0x4 | 0x5 | 0x9 | 0xE => {
+                let next_index = self.inner.index;
+                let control_flow = self.inner.execute(index, instruction, keyboard_state)?;
+
+                // the skipped instruction might be the 4 byte long F000 NNNN
+                if self.inner.index != next_index
+                    && self.inner.memory.get(next_index..next_index + 2) == Some(&[0xF0, 0x00])
+                {
+                    self.inner.index += mem::size_of::<Instruction>();
+                }
+
+                return Ok(control_flow);
+            }
+
+            _ => return self.inner.execute(index, instruction, keyboard_state),
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    pub fn new(options: super::Options, display_options: interfaces::DisplayOptions) -> Self {
+        let mut display_buffer = interfaces::DisplayBuffer::new(display_options);
+        display_buffer.half_resolution = true;
+
+        Self {
+            audio: Default::default(),
+            display_buffer,
+            inner: chip8::Backend::with_memory_size(options, None, MEMORY_SIZE),
+            program_exited: false,
+        }
+    }
+}
+
+impl Machine for Backend {
+    #[inline]
+    fn audio(&self) -> Option<&Audio> {
+        Some(&self.audio)
+    }
+
+    #[inline]
+    fn cpu_state(&self) -> CpuState<'_> {
+        self.inner.cpu_state()
+    }
+
+    fn display_buffer(&mut self) -> Result<Box<dyn Iterator<Item = u8> + '_>, BackendError> {
+        Ok(Box::new(self.display_buffer.get_flattened()))
+    }
+
+    fn display_buffer_size(&self) -> [usize; 2] {
+        [DISPLAY_BUFFER_WIDTH, DISPLAY_BUFFER_HEIGHT]
+    }
+
+    fn display_options_mut(&mut self) -> Option<&mut interfaces::DisplayOptions> {
+        Some(&mut self.display_buffer.options)
+    }
+
+    #[inline]
+    fn has_program_exited(&self) -> bool {
+        self.program_exited
+    }
+
+    #[inline]
+    fn instruction_set(&self) -> disassembler::InstructionSet {
+        disassembler::InstructionSet::XoChip
+    }
+
+    #[inline]
+    fn is_display_buffer_dirty(&self) -> bool {
+        self.display_buffer.is_dirty()
+    }
+
+    fn load(&mut self, font: Option<&[u8]>, program: &[u8]) -> Result<(), BackendError> {
+        let font = font.unwrap_or(&defaults::FONT);
+
+        self.inner.load(Some(&font[..chip8::FONT_SIZE]), program)?;
+
+        self.inner.memory[chip8::FONT_SIZE..chip8::FONT_SIZE + superchip::HIRES_FONT_SIZE]
+            .copy_from_slice(
+                font.get(chip8::FONT_SIZE..chip8::FONT_SIZE + superchip::HIRES_FONT_SIZE)
+                    .unwrap_or(
+                        &defaults::FONT
+                            [chip8::FONT_SIZE..chip8::FONT_SIZE + superchip::HIRES_FONT_SIZE],
+                    ),
+            );
+
+        Ok(())
+    }
+
+    #[inline]
+    fn options_mut(&mut self) -> &mut super::Options {
+        &mut self.inner.options
+    }
+
+    #[inline]
+    fn plane_count(&self) -> usize {
+        PLANE_COUNT
+    }
+
+    fn reset(&mut self) {
+        self.audio = Default::default();
+        self.display_buffer.half_resolution = true;
+        self.display_buffer.planes = 1;
+        self.program_exited = false;
+        self.inner.reset();
+        self.display_buffer.clear_planes(u8::MAX);
+    }
+
+    fn restore_state(&mut self, reader: &mut state::Reader) -> Result<(), BackendError> {
+        let program_exited = reader.read_bool()?;
+        let pattern = reader.read_bytes()?;
+        let pitch = reader.read_u8()?;
+
+        if pattern.len() != AUDIO_PATTERN_SIZE {
+            return Err(state::invalid());
+        }
+
+        self.inner
+            .restore_state_then(reader, |reader| self.display_buffer.restore_state(reader))?;
+
+        self.audio.pattern.copy_from_slice(pattern);
+        self.audio.pitch = pitch;
+        self.program_exited = program_exited;
+
+        Ok(())
+    }
+
+    fn save_state(&self, writer: &mut state::Writer) {
+        writer.write_bool(self.program_exited);
+        writer.write_bytes(&self.audio.pattern);
+        writer.write_u8(self.audio.pitch);
+
+        self.inner.save_state(writer);
+        self.display_buffer.save_state(writer);
+    }
+
+    #[inline]
+    fn seed(&self) -> u64 {
+        self.inner.seed()
+    }
+
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        self.inner.set_seed(seed)
+    }
+
+    fn step(
+        &mut self,
+        keyboard_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<ControlFlow<()>, BackendError> {
+        let (index, instruction) = self.inner.fetch()?;
+        self.execute(index, instruction, keyboard_state, persistent_storage)
+    }
+
+    #[inline]
+    fn supports_scrolling(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn timers(&self) -> &super::Timers {
+        &self.inner.timers
+    }
+
+    #[inline]
+    fn timers_mut(&mut self) -> &mut super::Timers {
+        &mut self.inner.timers
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new(
+            super::Options {
+                copy_and_shift: true,
+                display_wait: false,
+                increment_address: true,
+                machine_code: false,
+                quirky_jump: false,
+                reset_flag: false,
+            },
+            interfaces::DisplayOptions {
+                clip_sprites: false,
+                half_pixel_scrolling: false,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `steps` instructions of the program, with `data` at 0x300
+    fn run(program: &[u8], data: &[u8], steps: usize) -> Backend {
+        let mut memory = vec![0; 0x100 + data.len()];
+        memory[..program.len()].copy_from_slice(program);
+        memory[0x100..].copy_from_slice(data);
+
+        let mut backend = Backend::default();
+        backend.load(None, &memory).unwrap();
+
+        for _ in 0..steps {
+            let _ = backend
+                .step(
+                    &mut interfaces::KeypadState::new(),
+                    &mut [0; PERSISTENT_STORAGE_SIZE],
+                )
+                .unwrap();
+        }
+
+        backend
+    }
+
+    #[test]
+    fn long_load() {
+        let backend = run(&[0xF0, 0x00, 0x12, 0x34, 0x60, 0x01], &[], 2);
+
+        assert_eq!(backend.inner.registers.address, 0x1234);
+        assert_eq!(backend.inner.registers.general[0], 1);
+    }
+
+    #[test]
+    fn skip_long_load() {
+        for (skip, skipped) in [
+            (0x3000, true),
+            (0x3001, false),
+            (0x4001, true),
+            (0x5010, true),
+        ] {
+            let [high, low] = u16::to_be_bytes(skip);
+            let backend = run(&[high, low, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x05], &[], 2);
+
+            match skipped {
+                true => {
+                    assert_eq!(backend.inner.registers.address, 0);
+                    assert_eq!(backend.inner.registers.general[1], 5);
+                }
+                false => {
+                    assert_eq!(backend.inner.registers.address, 0x1234);
+                    assert_eq!(backend.inner.index, 0x206);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load_range() {
+        // v1 to v3 are 1, 2 and 3 and I points to 0x300
+        let setup = [0x61, 0x01, 0x62, 0x02, 0x63, 0x03, 0xA3, 0x00];
+
+        for (save, saved) in [(0x5132, [1, 2, 3]), (0x5312, [3, 2, 1])] {
+            let mut program = setup.to_vec();
+            program.extend_from_slice(&u16::to_be_bytes(save));
+
+            let backend = run(&program, &[0; 3], 5);
+            assert_eq!(backend.inner.memory[0x300..0x303], saved, "{:04X}", save);
+            assert_eq!(backend.inner.registers.address, 0x300);
+        }
+
+        for (load, loaded) in [(0x5463, [7, 8, 9]), (0x5643, [9, 8, 7])] {
+            let mut program = vec![0xA3, 0x00];
+            program.extend_from_slice(&u16::to_be_bytes(load));
+
+            let backend = run(&program, &[7, 8, 9], 2);
+            assert_eq!(
+                backend.inner.registers.general[4..7],
+                loaded,
+                "{:04X}",
+                load
+            );
+        }
+    }
+
+    #[test]
+    fn plane_selection() {
+        // the first row of the sprite is drawn on the first plane and the second on the second
+        let sprite = [0xFF, 0x0F];
+        let first_row = |backend: &mut Backend| -> Vec<u8> {
+            backend.display_buffer().unwrap().take(16).collect()
+        };
+
+        let mut backend = run(&[0xA3, 0x00, 0xD0, 0x01], &sprite, 2);
+        assert_eq!(first_row(&mut backend), [1; 16]);
+
+        let mut backend = run(&[0xF2, 0x01, 0xA3, 0x00, 0xD0, 0x01], &sprite, 3);
+        assert_eq!(first_row(&mut backend), [2; 16]);
+
+        // pixels are 2x2 in low resolution mode
+        let mut backend = run(&[0xF3, 0x01, 0xA3, 0x00, 0xD0, 0x01], &sprite, 3);
+        assert_eq!(first_row(&mut backend), [[1; 8], [3; 8]].concat());
+
+        let mut backend = run(&[0xF0, 0x01, 0xA3, 0x00, 0xD0, 0x01], &sprite, 3);
+        assert_eq!(first_row(&mut backend), [0; 16]);
+    }
+
+    #[test]
+    fn pitch() {
+        let backend = run(&[0x60, 0x70, 0xF0, 0x3A], &[], 2);
+
+        assert_eq!(backend.audio.pitch, 0x70);
+        assert_eq!(backend.audio.playback_rate(), 8000.0);
+        assert_eq!(Audio::default().playback_rate(), 4000.0);
+    }
+}
@@ -0,0 +1,44 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Packs bits least significant first, the way deflate and GIF's LZW do
+#[derive(Default)]
+pub struct BitWriter {
+    bit_count: u32,
+    bits: u32,
+    bytes: Vec<u8>,
+}
+
+impl BitWriter {
+    /// The bytes written so far, the last one padded with zeroes
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+
+        self.bytes
+    }
+
+    pub fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.bit_count;
+        self.bit_count += count;
+
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::path;
+
+use ruschip::backend::{self, disassembler};
+
+pub const USAGE: &str = "\
+Usage: ruschip [OPTIONS] [PROGRAM]
+
+Arguments:
+  [PROGRAM]  Path of the program to run, skips the menus if given
+
+Options:
+  -v, --variant <VARIANT>       Backend to use: chip8, vip, hires, chip48, schip1.0, schip,
+                                schip-modern or xochip [default: last used]
+  -f, --font <FONT>             Path of a custom font
+      --ips <IPS>               Instructions executed per second [default: 1680]
+      --screenshot-dir <DIR>    Directory to save screenshots (Ctrl+P) to [default: data directory]
+      --screenshot-scale <N>    Pixels per pixel of the display in screenshots, 1 to 16 [default: 1]
+      --seed <SEED>             Seed of the random number generator [default: random]
+      --headless                Run without a window or audio, then print the display to stdout
+      --cycles <N>              Number of 60 Hz cycles to run for in headless mode
+      --record <MOVIE>          Record the keypad input into a movie, saved when the program stops
+      --play <MOVIE>            Play a movie back, headless mode checks that it ends the same way
+      --screenshot <PATH>       Save the display as PNG once headless mode stops
+      --disassemble             Print a listing of the program instead of running it
+      --syntax <SYNTAX>         Syntax of the listing: cowgod or octo [default: cowgod]
+  -h, --help                    Print help
+  -V, --version                 Print version
+
+Quirks (the defaults depend on the variant):
+      --[no-]copy-and-shift        Copy vY to vX before shifting
+      --[no-]display-wait          Wait for the next 60 Hz cycle after drawing a sprite (DXYN)
+      --[no-]increment-address     Increment the address register after SAVE and LOAD
+      --[no-]machine-code          Run 0NNN as a call to 1802 machine code (hybrid VIP programs)
+      --[no-]quirky-jump           Jump to NNN plus vX instead of v0 (BNNN)
+      --[no-]reset-flag            Reset vF after AND, OR and XOR
+      --[no-]clip-sprites          Clip sprites at the edge of the screen instead of wrapping
+      --[no-]half-pixel-scrolling  Scroll by half pixels in low resolution mode
+
+Unless running headless, the options that aren't given are taken from config.toml in the data
+directory, which is rewritten with the current settings whenever they change. Headless screenshots
+take their colors, and their scale if it isn't given, from it too.
+";
+
+pub struct Arguments {
+    pub cycles: Option<u64>,
+    pub disassemble: bool,
+    pub font: Option<path::PathBuf>,
+    pub headless: bool,
+    pub ips: Option<usize>,
+    pub play: Option<path::PathBuf>,
+    pub program: Option<path::PathBuf>,
+    pub quirks: backend::Quirks,
+    pub record: Option<path::PathBuf>,
+    /// Where to save the display once headless mode stops
+    pub screenshot: Option<path::PathBuf>,
+    pub screenshot_directory: Option<path::PathBuf>,
+    pub screenshot_scale: Option<u32>,
+    pub seed: Option<u64>,
+    pub syntax: disassembler::Syntax,
+    /// Index into [`backend::VARIANTS`], the configured one is used if not given
+    pub variant: Option<usize>,
+}
+
+pub enum Command {
+    Help,
+    Run(Box<Arguments>),
+    Version,
+}
+
+impl Arguments {
+    pub fn instructions_per_tick(&self) -> u16 {
+        self.ips
+            .map_or(backend::DEFAULT_INSTRUCTIONS_PER_TICK, |ips| {
+                (ips / backend::TICKS_PER_SECOND).clamp(1, u16::MAX as usize) as u16
+            })
+    }
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut arguments = Arguments {
+        cycles: None,
+        disassemble: false,
+        font: None,
+        headless: false,
+        ips: None,
+        play: None,
+        program: None,
+        quirks: Default::default(),
+        record: None,
+        screenshot: None,
+        screenshot_directory: None,
+        screenshot_scale: None,
+        seed: None,
+        syntax: disassembler::Syntax::Cowgod,
+        variant: None,
+    };
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if arguments.program.replace(arg.into()).is_some() {
+                return Err("more than one program was given".to_string());
+            }
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("'{}' expects a value", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+
+            "-v" | "--variant" => {
+                let id = value()?;
+                arguments.variant = Some(
+                    backend::VARIANTS
+                        .iter()
+                        .position(|variant| variant.id == id)
+                        .ok_or_else(|| format!("unknown variant '{}'", id))?,
+                );
+            }
+
+            "-f" | "--font" => arguments.font = Some(value()?.into()),
+
+            "--ips" => {
+                let ips = value()?;
+                arguments.ips = Some(
+                    ips.parse()
+                        .ok()
+                        .filter(|&ips| ips > 0)
+                        .ok_or_else(|| format!("invalid number of instructions '{}'", ips))?,
+                );
+            }
+
+            "--screenshot-dir" => arguments.screenshot_directory = Some(value()?.into()),
+
+            "--screenshot-scale" => {
+                let scale = value()?;
+                arguments.screenshot_scale = Some(
+                    scale
+                        .parse()
+                        .ok()
+                        .filter(|&scale| (1..=16).contains(&scale))
+                        .ok_or_else(|| format!("invalid screenshot scale '{}'", scale))?,
+                );
+            }
+
+            "--seed" => {
+                let seed = value()?;
+                arguments.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("invalid seed '{}'", seed))?,
+                );
+            }
+
+            "--headless" => arguments.headless = true,
+
+            "--cycles" => {
+                let cycles = value()?;
+                arguments.cycles = Some(
+                    cycles
+                        .parse()
+                        .map_err(|_| format!("invalid number of cycles '{}'", cycles))?,
+                );
+            }
+
+            "--record" => arguments.record = Some(value()?.into()),
+            "--play" => arguments.play = Some(value()?.into()),
+
+            "--screenshot" => arguments.screenshot = Some(value()?.into()),
+
+            "--disassemble" => arguments.disassemble = true,
+
+            "--syntax" => {
+                arguments.syntax = match value()?.as_str() {
+                    "cowgod" => disassembler::Syntax::Cowgod,
+                    "octo" => disassembler::Syntax::Octo,
+                    syntax => return Err(format!("unknown syntax '{}'", syntax)),
+                }
+            }
+
+            _ => {
+                let name = flag.trim_start_matches("--");
+                let (name, enabled) = match name.strip_prefix("no-") {
+                    Some(name) => (name, false),
+                    None => (name, true),
+                };
+
+                match arguments.quirks.get_mut(name) {
+                    Some(quirk) if inline_value.is_none() => *quirk = Some(enabled),
+                    _ => return Err(format!("unrecognized option '{}'", flag)),
+                }
+            }
+        }
+    }
+
+    if arguments.disassemble && arguments.program.is_none() {
+        return Err("'--disassemble' needs a program to list".to_string());
+    }
+
+    if arguments.headless && arguments.program.is_none() {
+        return Err("'--headless' needs a program to run".to_string());
+    }
+
+    if arguments.play.is_some() && arguments.record.is_some() {
+        return Err("'--play' and '--record' can't be used together".to_string());
+    }
+
+    if arguments.headless && arguments.record.is_some() {
+        return Err("'--record' needs the window, use '--play' to check a movie".to_string());
+    }
+
+    if arguments.screenshot.is_some() && !arguments.headless {
+        return Err("'--screenshot' needs '--headless', use Ctrl+P in the window".to_string());
+    }
+
+    if arguments.headless && arguments.cycles.is_none() && arguments.play.is_none() {
+        return Err(
+            "'--headless' needs the number of '--cycles' to run for or a movie to '--play'"
+                .to_string(),
+        );
+    }
+
+    Ok(Command::Run(Box::new(arguments)))
+}
@@ -0,0 +1,651 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::error;
+use std::fmt;
+use std::path;
+
+use eframe::egui;
+
+use crate::backend;
+use crate::defaults;
+use crate::frontend;
+
+/// Name of the config file in the data directory
+pub const FILE_NAME: &str = "config.toml";
+
+const KEYS: [egui::Key; 73] = [
+    egui::Key::ArrowDown,
+    egui::Key::ArrowLeft,
+    egui::Key::ArrowRight,
+    egui::Key::ArrowUp,
+    egui::Key::Escape,
+    egui::Key::Tab,
+    egui::Key::Backspace,
+    egui::Key::Enter,
+    egui::Key::Space,
+    egui::Key::Insert,
+    egui::Key::Delete,
+    egui::Key::Home,
+    egui::Key::End,
+    egui::Key::PageUp,
+    egui::Key::PageDown,
+    egui::Key::Minus,
+    egui::Key::PlusEquals,
+    egui::Key::Num0,
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+    egui::Key::A,
+    egui::Key::B,
+    egui::Key::C,
+    egui::Key::D,
+    egui::Key::E,
+    egui::Key::F,
+    egui::Key::G,
+    egui::Key::H,
+    egui::Key::I,
+    egui::Key::J,
+    egui::Key::K,
+    egui::Key::L,
+    egui::Key::M,
+    egui::Key::N,
+    egui::Key::O,
+    egui::Key::P,
+    egui::Key::Q,
+    egui::Key::R,
+    egui::Key::S,
+    egui::Key::T,
+    egui::Key::U,
+    egui::Key::V,
+    egui::Key::W,
+    egui::Key::X,
+    egui::Key::Y,
+    egui::Key::Z,
+    egui::Key::F1,
+    egui::Key::F2,
+    egui::Key::F3,
+    egui::Key::F4,
+    egui::Key::F5,
+    egui::Key::F6,
+    egui::Key::F7,
+    egui::Key::F8,
+    egui::Key::F9,
+    egui::Key::F10,
+    egui::Key::F11,
+    egui::Key::F12,
+    egui::Key::F13,
+    egui::Key::F14,
+    egui::Key::F15,
+    egui::Key::F16,
+    egui::Key::F17,
+    egui::Key::F18,
+    egui::Key::F19,
+    egui::Key::F20,
+];
+
+/// Settings that persist between sessions
+#[derive(Clone)]
+pub struct Config {
+    pub colors: frontend::Colors,
+    pub font_path: Option<path::PathBuf>,
+    pub instructions_per_tick: u16,
+    pub key_map: frontend::KeyMap,
+    pub program_path: Option<path::PathBuf>,
+    pub quirks: backend::Quirks,
+    /// Save every frame as PNG and the buzzer as WAV along with the GIF of a clip
+    pub raw_clips: bool,
+    pub rewind_depth: u32,
+    /// Where screenshots are saved, a directory in the data directory if not given
+    pub screenshot_directory: Option<path::PathBuf>,
+    /// Pixels per pixel of the display in screenshots
+    pub screenshot_scale: u32,
+    /// Seed of the random number generator, a new one is picked every start if not given
+    pub seed: Option<u64>,
+    pub speed: u32,
+    /// Identifier of the last used variant
+    pub variant: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub kind: ConfigErrorKind,
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    InvalidLine,
+    InvalidValue(String),
+    UnknownKey(String),
+    UnknownTable(String),
+}
+
+#[derive(Clone)]
+pub(crate) enum Value {
+    Boolean(bool),
+    Integer(u64),
+    String(String),
+}
+
+impl Config {
+    /// Parse the subset of TOML that [`Config`] is written as, the keys that aren't given keep their
+    /// defaults
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        parse_document(
+            text,
+            |table| match table {
+                "colors" | "keys" | "quirks" => Ok(()),
+                _ => Err(ConfigErrorKind::UnknownTable(table.to_string())),
+            },
+            |table, key, value| config.set(table, key, value),
+        )?;
+
+        Ok(config)
+    }
+
+    /// Set a key of a table the way it's written in the config file
+    pub(crate) fn set(
+        &mut self,
+        table: &str,
+        key: &str,
+        value: Value,
+    ) -> Result<(), ConfigErrorKind> {
+        let invalid = || ConfigErrorKind::InvalidValue(key.to_string());
+        let unknown = || ConfigErrorKind::UnknownKey(key.to_string());
+
+        match table {
+            "colors" => {
+                let color = match key {
+                    "active" => &mut self.colors.active,
+                    "inactive" => &mut self.colors.inactive,
+                    "overlap" => &mut self.colors.overlap,
+                    "secondary" => &mut self.colors.secondary,
+                    _ => return Err(unknown()),
+                };
+
+                *color = value
+                    .into_string()
+                    .as_deref()
+                    .and_then(parse_color)
+                    .ok_or_else(invalid)?;
+            }
+
+            "keys" => {
+                let i = usize::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&i| key.len() == 1 && i < backend::KEY_COUNT)
+                    .ok_or_else(unknown)?;
+
+                let mut keys = Vec::new();
+
+                for name in value.into_string().ok_or_else(invalid)?.split(',') {
+                    let name = name.trim();
+                    if name.is_empty() {
+                        continue;
+                    }
+
+                    let key = KEYS
+                        .into_iter()
+                        .find(|key| key.name() == name)
+                        .ok_or_else(invalid)?;
+
+                    if !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+
+                self.key_map[i] = keys;
+            }
+
+            "quirks" => {
+                let quirk = self.quirks.get_mut(key).ok_or_else(unknown)?;
+                *quirk = Some(value.into_boolean().ok_or_else(invalid)?);
+            }
+
+            "" => match key {
+                "font" => self.font_path = Some(value.into_string().ok_or_else(invalid)?.into()),
+                "instructions-per-frame" => {
+                    self.instructions_per_tick = value
+                        .into_integer()
+                        .and_then(|n| u16::try_from(n).ok())
+                        .filter(|&n| n > 0)
+                        .ok_or_else(invalid)?
+                }
+                "program" => {
+                    self.program_path = Some(value.into_string().ok_or_else(invalid)?.into())
+                }
+                "raw-clips" => self.raw_clips = value.into_boolean().ok_or_else(invalid)?,
+                "rewind-depth" => {
+                    self.rewind_depth = value
+                        .into_integer()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .ok_or_else(invalid)?
+                }
+                "screenshot-directory" => {
+                    self.screenshot_directory =
+                        Some(value.into_string().ok_or_else(invalid)?.into())
+                }
+                "screenshot-scale" => {
+                    self.screenshot_scale = value
+                        .into_integer()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .filter(|n| frontend::SCREENSHOT_SCALE_RANGE.contains(n))
+                        .ok_or_else(invalid)?
+                }
+                "seed" => self.seed = Some(value.into_integer().ok_or_else(invalid)?),
+                "speed" => {
+                    self.speed = value
+                        .into_integer()
+                        .and_then(|n| u32::try_from(n).ok())
+                        .filter(|n| frontend::SPEED_RANGE.contains(n))
+                        .ok_or_else(invalid)?
+                }
+                "variant" => self.variant = Some(value.into_string().ok_or_else(invalid)?),
+                _ => return Err(unknown()),
+            },
+
+            _ => return Err(ConfigErrorKind::UnknownTable(table.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            colors: defaults::COLORS,
+            font_path: None,
+            instructions_per_tick: backend::DEFAULT_INSTRUCTIONS_PER_TICK,
+            key_map: frontend::default_key_map(),
+            program_path: None,
+            quirks: backend::Quirks::default(),
+            raw_clips: false,
+            rewind_depth: frontend::DEFAULT_REWIND_DEPTH,
+            screenshot_directory: None,
+            screenshot_scale: *frontend::SCREENSHOT_SCALE_RANGE.start(),
+            seed: None,
+            speed: frontend::DEFAULT_SPEED,
+            variant: None,
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(variant) = &self.variant {
+            write!(f, "variant = ")?;
+            write_string(f, variant)?;
+            writeln!(f)?;
+        }
+
+        for (key, path) in [
+            ("font", &self.font_path),
+            ("program", &self.program_path),
+            ("screenshot-directory", &self.screenshot_directory),
+        ] {
+            if let Some(path) = path.as_ref().and_then(|path| path.to_str()) {
+                write!(f, "{} = ", key)?;
+                write_string(f, path)?;
+                writeln!(f)?;
+            }
+        }
+
+        writeln!(f, "instructions-per-frame = {}", self.instructions_per_tick)?;
+        writeln!(f, "raw-clips = {}", self.raw_clips)?;
+        writeln!(f, "rewind-depth = {}", self.rewind_depth)?;
+        writeln!(f, "screenshot-scale = {}", self.screenshot_scale)?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed = {}", seed)?;
+        }
+        writeln!(f, "speed = {}", self.speed)?;
+
+        writeln!(f, "\n[colors]")?;
+        for (key, color) in [
+            ("active", self.colors.active),
+            ("inactive", self.colors.inactive),
+            ("overlap", self.colors.overlap),
+            ("secondary", self.colors.secondary),
+        ] {
+            writeln!(
+                f,
+                "{} = \"#{:02X}{:02X}{:02X}\"",
+                key,
+                color.r(),
+                color.g(),
+                color.b()
+            )?;
+        }
+
+        writeln!(f, "\n[keys]")?;
+        for (i, keys) in self.key_map.iter().enumerate() {
+            let names: Vec<&str> = keys.iter().map(|key| key.name()).collect();
+
+            write!(f, "{:X} = ", i)?;
+            write_string(f, &names.join(", "))?;
+            writeln!(f)?;
+        }
+
+        writeln!(f, "\n[quirks]")?;
+        for (key, value) in [
+            ("clip-sprites", self.quirks.clip_sprites),
+            ("copy-and-shift", self.quirks.copy_and_shift),
+            ("display-wait", self.quirks.display_wait),
+            ("half-pixel-scrolling", self.quirks.half_pixel_scrolling),
+            ("increment-address", self.quirks.increment_address),
+            ("machine-code", self.quirks.machine_code),
+            ("quirky-jump", self.quirks.quirky_jump),
+            ("reset-flag", self.quirks.reset_flag),
+        ] {
+            if let Some(value) = value {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, {}", self.line, self.kind)
+    }
+}
+
+impl fmt::Display for ConfigErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine => write!(f, "expected a table or a key-value pair"),
+            Self::InvalidValue(key) => write!(f, "invalid value for '{}'", key),
+            Self::UnknownKey(key) => write!(f, "unknown key '{}'", key),
+            Self::UnknownTable(table) => write!(f, "unknown table '{}'", table),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+impl Value {
+    pub(crate) fn into_boolean(self) -> Option<bool> {
+        match self {
+            Self::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_integer(self) -> Option<u64> {
+        match self {
+            Self::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_string(self) -> Option<String> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let Some(text) = text.strip_prefix('"') else {
+            let text = text.split_once('#').map_or(text, |(text, _)| text).trim();
+
+            return match text {
+                "false" => Some(Self::Boolean(false)),
+                "true" => Some(Self::Boolean(true)),
+                _ => text.parse().ok().map(Self::Integer),
+            };
+        };
+
+        let mut value = String::new();
+        let mut chars = text.chars();
+
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => value.push(match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    c @ ('"' | '\\') => c,
+                    _ => return None,
+                }),
+                c => value.push(c),
+            }
+        }
+
+        let rest = chars.as_str().trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return None;
+        }
+
+        Some(Self::String(value))
+    }
+}
+
+/// Parse a document in the subset of TOML the config is written in, `table` is called with the name
+/// of every table and `entry` with every key-value pair along with the table it's in
+pub(crate) fn parse_document(
+    text: &str,
+    mut table: impl FnMut(&str) -> Result<(), ConfigErrorKind>,
+    mut entry: impl FnMut(&str, &str, Value) -> Result<(), ConfigErrorKind>,
+) -> Result<(), ConfigError> {
+    let mut current_table = "";
+
+    for (i, line) in text.lines().enumerate() {
+        let error = |kind| ConfigError { kind, line: i + 1 };
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current_table = name.trim();
+            table(current_table).map_err(error)?;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error(ConfigErrorKind::InvalidLine));
+        };
+        let key = key.trim();
+
+        let value = Value::parse(value.trim())
+            .ok_or_else(|| error(ConfigErrorKind::InvalidValue(key.to_string())))?;
+
+        entry(current_table, key, value).map_err(error)?;
+    }
+
+    Ok(())
+}
+
+fn parse_color(text: &str) -> Option<egui::Color32> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let [_, r, g, b] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+
+    Some(egui::Color32::from_rgb(r, g, b))
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut key_map = frontend::default_key_map();
+        key_map[0x5] = vec![egui::Key::W, egui::Key::ArrowUp];
+        key_map[0xF] = Vec::new();
+
+        let config = Config {
+            colors: frontend::Colors {
+                active: egui::Color32::from_rgb(0x12, 0x34, 0x56),
+                inactive: egui::Color32::from_rgb(0xAB, 0xCD, 0xEF),
+                overlap: egui::Color32::BLACK,
+                secondary: egui::Color32::WHITE,
+            },
+            font_path: Some("fonts/big \"digits\".bin".into()),
+            instructions_per_tick: 1000,
+            key_map,
+            program_path: Some("C:\\programs\\pong.ch8".into()),
+            quirks: backend::Quirks {
+                clip_sprites: Some(false),
+                display_wait: Some(true),
+                quirky_jump: Some(true),
+                ..Default::default()
+            },
+            raw_clips: true,
+            rewind_depth: 0,
+            screenshot_directory: Some("screenshots\n\tshots".into()),
+            screenshot_scale: 16,
+            seed: Some(u64::MAX),
+            speed: 50,
+            variant: Some("schip".to_string()),
+        };
+
+        let mut parsed = Config::parse(&config.to_string()).unwrap();
+
+        assert_eq!(parsed.colors.active, config.colors.active);
+        assert_eq!(parsed.colors.inactive, config.colors.inactive);
+        assert_eq!(parsed.colors.overlap, config.colors.overlap);
+        assert_eq!(parsed.colors.secondary, config.colors.secondary);
+        assert_eq!(parsed.font_path, config.font_path);
+        assert_eq!(parsed.instructions_per_tick, config.instructions_per_tick);
+        assert_eq!(parsed.key_map, config.key_map);
+        assert_eq!(parsed.program_path, config.program_path);
+        assert_eq!(parsed.raw_clips, config.raw_clips);
+        assert_eq!(parsed.rewind_depth, config.rewind_depth);
+        assert_eq!(parsed.screenshot_directory, config.screenshot_directory);
+        assert_eq!(parsed.screenshot_scale, config.screenshot_scale);
+        assert_eq!(parsed.seed, config.seed);
+        assert_eq!(parsed.speed, config.speed);
+        assert_eq!(parsed.variant, config.variant);
+
+        for (quirk, value) in [
+            ("clip-sprites", Some(false)),
+            ("copy-and-shift", None),
+            ("display-wait", Some(true)),
+            ("quirky-jump", Some(true)),
+            ("reset-flag", None),
+        ] {
+            assert_eq!(parsed.quirks.get_mut(quirk).copied(), Some(value));
+        }
+    }
+
+    #[test]
+    fn strings() {
+        let config = Config::parse(
+            "variant = \"a \\\"quoted\\\" \\\\ name\" # comment\n\
+            program = \"tab\\tand\\nnewline\"",
+        )
+        .unwrap();
+
+        assert_eq!(config.variant.as_deref(), Some("a \"quoted\" \\ name"));
+        assert_eq!(config.program_path, Some("tab\tand\nnewline".into()));
+
+        for text in [
+            "variant = \"unterminated",
+            "variant = \"unknown \\escape\"",
+            "variant = \"trailing\" text",
+        ] {
+            let error = Config::parse(text).err().unwrap();
+            assert!(
+                matches!(error.kind, ConfigErrorKind::InvalidValue(_)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for (text, line, expected) in [
+            ("speed = 50\nnot a pair", 2, ConfigErrorKind::InvalidLine),
+            (
+                "# comment\n\nspeed = 1000",
+                3,
+                ConfigErrorKind::InvalidValue("speed".to_string()),
+            ),
+            (
+                "raw-clips = 1",
+                1,
+                ConfigErrorKind::InvalidValue("raw-clips".to_string()),
+            ),
+            (
+                "[colors]\nactive = \"#12345\"",
+                2,
+                ConfigErrorKind::InvalidValue("active".to_string()),
+            ),
+            (
+                "[keys]\n0 = \"X\"\n10 = \"Y\"",
+                3,
+                ConfigErrorKind::UnknownKey("10".to_string()),
+            ),
+            (
+                "[keys]\n1 = \"Nonexistent\"",
+                2,
+                ConfigErrorKind::InvalidValue("1".to_string()),
+            ),
+            (
+                "volume = 10",
+                1,
+                ConfigErrorKind::UnknownKey("volume".to_string()),
+            ),
+            (
+                "[quirks]\nwrap = true",
+                2,
+                ConfigErrorKind::UnknownKey("wrap".to_string()),
+            ),
+            (
+                "speed = 50\n\n[audio]",
+                3,
+                ConfigErrorKind::UnknownTable("audio".to_string()),
+            ),
+        ] {
+            let error = Config::parse(text).err().unwrap();
+
+            assert_eq!(error.line, line, "{}", text);
+            assert_eq!(error.kind, expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::sync;
+
+use crate::config::{self, ConfigErrorKind};
+
+const DATABASE: &str = include_str!("../assets/database.toml");
+
+static ENTRIES: sync::OnceLock<HashMap<String, Entry>> = sync::OnceLock::new();
+
+/// What the bundled database knows about a program
+#[derive(Default)]
+pub struct Entry {
+    pub authors: Option<String>,
+    /// Settings as tables and key-value pairs of the config file
+    settings: Vec<(String, String, config::Value)>,
+    pub title: String,
+}
+
+impl Entry {
+    /// Override the settings that are known for the program
+    pub fn apply(&self, config: &mut config::Config) {
+        for (table, key, value) in &self.settings {
+            // the settings were checked when the database was parsed
+            let _ = config.set(table, key, value.clone());
+        }
+    }
+}
+
+/// Look up a program by its SHA-1, see [`crate::sha1::hex_digest`]
+pub fn find(hash: &str) -> Option<&'static Entry> {
+    ENTRIES
+        .get_or_init(|| {
+            parse(DATABASE).unwrap_or_else(|error| {
+                eprintln!("couldn't load the bundled database, {}", error);
+                HashMap::new()
+            })
+        })
+        .get(hash)
+}
+
+/// Parse the database, every program has a table named after its SHA-1 with the title, the authors
+/// and the settings of the root table of the config file, the other tables of the config file are
+/// its subtables
+fn parse(text: &str) -> Result<HashMap<String, Entry>, config::ConfigError> {
+    let mut entries: HashMap<String, Entry> = HashMap::new();
+
+    config::parse_document(
+        text,
+        |name| {
+            let (hash, table) = split_table(name);
+
+            if hash.len() != 40
+                || !hash
+                    .bytes()
+                    .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+                || !matches!(table, "" | "colors" | "keys" | "quirks")
+            {
+                return Err(ConfigErrorKind::UnknownTable(name.to_string()));
+            }
+
+            Ok(())
+        },
+        |table, key, value| {
+            let (hash, table) = split_table(table);
+            let invalid = || ConfigErrorKind::InvalidValue(key.to_string());
+
+            if hash.is_empty() {
+                return Err(ConfigErrorKind::UnknownKey(key.to_string()));
+            }
+
+            let entry = entries.entry(hash.to_string()).or_default();
+
+            match (table, key) {
+                ("", "authors") => entry.authors = Some(value.into_string().ok_or_else(invalid)?),
+                ("", "title") => entry.title = value.into_string().ok_or_else(invalid)?,
+                (
+                    "",
+                    "font"
+                    | "program"
+                    | "raw-clips"
+                    | "rewind-depth"
+                    | "screenshot-directory"
+                    | "seed",
+                ) => return Err(ConfigErrorKind::UnknownKey(key.to_string())),
+                _ => {
+                    config::Config::default().set(table, key, value.clone())?;
+                    entry
+                        .settings
+                        .push((table.to_string(), key.to_string(), value));
+                }
+            }
+
+            Ok(())
+        },
+    )?;
+
+    Ok(entries)
+}
+
+/// Split the name of a table into the hash and the subtable
+fn split_table(table: &str) -> (&str, &str) {
+    table.split_once('.').unwrap_or((table, ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha1;
+
+    #[test]
+    fn bundled() {
+        let entries = parse(DATABASE).unwrap();
+
+        for (hash, entry) in &entries {
+            assert!(!entry.title.is_empty(), "{} has no title", hash);
+            assert!(find(hash).is_some_and(|found| found.title == entry.title));
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let hash = sha1::hex_digest(b"");
+
+        for text in [
+            "title = \"No table\"".to_string(),
+            format!("[{}]\nseed = 1", hash),
+            format!("[{}]\ninstructions-per-frame = 0", hash),
+            format!("[{}.display]\nscale = 1", hash),
+            format!("[{}]\ntitle = \"Uppercase\"", hash.to_ascii_uppercase()),
+        ] {
+            assert!(parse(&text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let hash = sha1::hex_digest(&[0x12, 0x00]);
+        let text = format!(
+            "[{0}]\n\
+            title = \"Loop\"\n\
+            authors = \"Someone\"\n\
+            variant = \"schip\"\n\
+            instructions-per-frame = 30\n\
+            [{0}.keys]\n\
+            5 = \"W, Up\"\n\
+            [{0}.quirks]\n\
+            quirky-jump = true\n",
+            hash
+        );
+
+        let entries = parse(&text).unwrap();
+        assert!(!entries.contains_key(&sha1::hex_digest(&[])));
+
+        let entry = &entries[&hash];
+        assert_eq!(entry.title, "Loop");
+        assert_eq!(entry.authors.as_deref(), Some("Someone"));
+
+        let mut config = config::Config::default();
+        entry.apply(&mut config);
+        assert_eq!(config.variant.as_deref(), Some("schip"));
+        assert_eq!(config.instructions_per_tick, 30);
+        assert_eq!(config.key_map[5].len(), 2);
+        assert_eq!(config.quirks.get_mut("quirky-jump"), Some(&mut Some(true)));
+    }
+}
@@ -0,0 +1,88 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::f32::consts;
+use std::sync;
+#[cfg(feature = "audio")]
+use std::time;
+
+use crate::backend::xochip;
+
+pub const SAMPLE_RATE: u32 = 48000;
+
+/// A sound source that plays either a sine wave or an XO-CHIP audio pattern
+pub struct Buzzer {
+    audio: sync::Arc<sync::Mutex<Option<xochip::Audio>>>,
+    frequency: f32,
+    phase: f32,
+}
+
+impl Buzzer {
+    pub fn new(frequency: f32, audio: sync::Arc<sync::Mutex<Option<xochip::Audio>>>) -> Self {
+        Self {
+            audio,
+            frequency,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Iterator for Buzzer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let audio = *self.audio.lock().unwrap();
+
+        let sample = match audio {
+            None => {
+                self.phase = (self.phase + self.frequency / SAMPLE_RATE as f32) % 1.0;
+                (2.0 * consts::PI * self.phase).sin()
+            }
+            Some(audio) => {
+                let bit_count = (xochip::AUDIO_PATTERN_SIZE * u8::BITS as usize) as f32;
+                self.phase = (self.phase + audio.playback_rate() / SAMPLE_RATE as f32) % bit_count;
+
+                let bit = self.phase as usize;
+                let byte = audio.pattern[bit / u8::BITS as usize];
+
+                match byte & (0x80 >> (bit % u8::BITS as usize)) {
+                    0 => -1.0,
+                    _ => 1.0,
+                }
+            }
+        };
+
+        Some(sample)
+    }
+}
+
+#[cfg(feature = "audio")]
+impl rodio::Source for Buzzer {
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<time::Duration> {
+        None
+    }
+}
@@ -0,0 +1,193 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync;
+
+use crate::backend::{self, xochip, BackendError, Machine};
+use crate::{gif, png, wav};
+
+use super::buzzer::{self, Buzzer};
+use super::speaker::BUZZ_FREQUENCY;
+
+/// Shortest delay between the frames of a GIF that viewers honor, in hundredths of a second
+const MIN_FRAME_DELAY: u64 = 2;
+const SAMPLES_PER_TICK: usize = buzzer::SAMPLE_RATE as usize / backend::TICKS_PER_SECOND;
+const VOLUME: f32 = 8192.0;
+
+/// The display and the buzzer over a stretch of gameplay, for exporting clips
+pub struct Capture {
+    audio: sync::Arc<sync::Mutex<Option<xochip::Audio>>>,
+    buzzer: Buzzer,
+    /// The display whenever it changed, along with the tick it changed in, whose audio it's shown
+    /// with
+    frames: Vec<(u64, Vec<u8>)>,
+    samples: Vec<i16>,
+    size: [usize; 2],
+    ticks: u64,
+}
+
+impl Capture {
+    /// Encode the display of every tick as a PNG, the frames of a 60 FPS video
+    pub fn frame_sequence<'a>(
+        &'a self,
+        palette: &'a [[u8; 3]; 4],
+    ) -> impl Iterator<Item = Vec<u8>> + 'a {
+        let [width, height] = self.size;
+
+        (0..self.ticks).scan(0, move |i, tick| {
+            while self
+                .frames
+                .get(*i + 1)
+                .is_some_and(|&(frame_tick, _)| frame_tick <= tick)
+            {
+                *i += 1;
+            }
+
+            Some(png::encode(width, height, &self.frames[*i].1, palette))
+        })
+    }
+
+    /// Encode the frames as an animated GIF
+    ///
+    /// Frames that are too short for viewers to show are replaced by the frame after them
+    pub fn gif(&self, palette: &[[u8; 3]; 4]) -> Vec<u8> {
+        let centiseconds = |ticks: u64| ticks * 100 / backend::TICKS_PER_SECOND as u64;
+
+        let mut frames: Vec<(u64, &[u8])> = Vec::with_capacity(self.frames.len());
+        for (ticks, pixels) in &self.frames {
+            let time = centiseconds(*ticks);
+
+            match frames.last_mut() {
+                Some((_, last)) if *last == pixels.as_slice() => (),
+                Some((start, last)) if time - *start < MIN_FRAME_DELAY => *last = pixels,
+                _ => frames.push((time, pixels)),
+            }
+        }
+
+        let end = centiseconds(self.ticks).max(frames[frames.len() - 1].0 + MIN_FRAME_DELAY);
+        let [width, height] = self.size;
+
+        gif::encode(
+            width,
+            height,
+            palette,
+            frames.iter().enumerate().map(|(i, &(start, pixels))| {
+                let next = frames.get(i + 1).map_or(end, |&(next, _)| next);
+                ((next - start).min(u16::MAX as u64) as u16, pixels)
+            }),
+        )
+    }
+
+    /// Start capturing with the display as it is
+    pub(super) fn new(backend: &mut dyn Machine) -> Result<Self, BackendError> {
+        let audio = sync::Arc::new(sync::Mutex::new(None));
+
+        Ok(Self {
+            buzzer: Buzzer::new(BUZZ_FREQUENCY, audio.clone()),
+            audio,
+            frames: vec![(0, display(backend)?)],
+            samples: Vec::new(),
+            size: backend.display_buffer_size(),
+            ticks: 0,
+        })
+    }
+
+    /// Capture the tick that just ran, returns true if the display changed, which marks it as clean
+    pub(super) fn push(&mut self, backend: &mut dyn Machine) -> Result<bool, BackendError> {
+        let tick = self.ticks;
+        self.ticks += 1;
+
+        match backend.timers().sound {
+            0 => self
+                .samples
+                .resize(self.samples.len() + SAMPLES_PER_TICK, 0),
+            _ => {
+                *self.audio.lock().unwrap() = backend.audio().copied();
+                self.samples.extend(
+                    self.buzzer
+                        .by_ref()
+                        .take(SAMPLES_PER_TICK)
+                        .map(|sample| (sample * VOLUME) as i16),
+                );
+            }
+        }
+
+        if !backend.is_display_buffer_dirty() {
+            return Ok(false);
+        }
+
+        let pixels = display(backend)?;
+        match self.frames.last_mut() {
+            // the display the capture started with is replaced if it changed in the first tick
+            Some((last_tick, last)) if *last_tick == tick => *last = pixels,
+            _ => self.frames.push((tick, pixels)),
+        }
+
+        Ok(true)
+    }
+
+    /// Encode the buzzer as a WAV file, it lines up with [`Capture::frame_sequence`]
+    pub fn wav(&self) -> Vec<u8> {
+        wav::encode(buzzer::SAMPLE_RATE, &self.samples)
+    }
+}
+
+/// The colors of the display's pixels, marks the display as clean
+fn display(backend: &mut dyn Machine) -> Result<Vec<u8>, BackendError> {
+    Ok(backend
+        .display_buffer()?
+        .map(|pixel| pixel & 0b11)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{chip8, interfaces};
+
+    const PALETTE: [[u8; 3]; 4] = [[0; 3], [0xFF; 3], [0; 3], [0; 3]];
+
+    #[test]
+    fn frames_line_up_with_audio() {
+        let mut backend = chip8::Backend::default();
+        // sound the buzzer for 3 ticks, draw and loop
+        backend
+            .load(None, &[0x60, 0x03, 0xF0, 0x18, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+
+        let mut capture = Capture::new(&mut backend).unwrap();
+        let blank = png::encode(64, 32, &capture.frames[0].1, &PALETTE);
+
+        for _ in 0..5 {
+            backend
+                .tick(10, &mut interfaces::KeypadState::new(), &mut [])
+                .unwrap();
+            capture.push(&mut backend).unwrap();
+        }
+
+        let frames: Vec<Vec<u8>> = capture.frame_sequence(&PALETTE).collect();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(capture.samples.len(), 5 * SAMPLES_PER_TICK);
+
+        // the sprite is drawn in the first tick, along with the first tick of the buzzer
+        assert!(frames.iter().all(|frame| *frame != blank));
+        assert!(capture.samples[..SAMPLES_PER_TICK]
+            .iter()
+            .any(|&sample| sample != 0));
+        assert!(capture.samples[3 * SAMPLES_PER_TICK..]
+            .iter()
+            .all(|&sample| sample == 0));
+    }
+}
@@ -0,0 +1,147 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+
+use crate::backend::{self, interfaces};
+
+/// Breakpoints and the pending step of a debugging session
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: BTreeSet<usize>,
+    /// Address execution was last halted at, it doesn't halt again right away when resuming
+    halted_at: Option<usize>,
+    target: Option<Target>,
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    /// Halt before executing the instruction at the address
+    Address(usize),
+    /// Halt once the stack is no deeper than the depth
+    Return(usize),
+}
+
+impl Debugger {
+    /// Whether a step over, step out or run to cursor is still in progress
+    #[inline]
+    pub fn has_target(&self) -> bool {
+        self.target.is_some()
+    }
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Forget the pending step, the breakpoints are kept
+    pub fn reset(&mut self) {
+        self.halted_at = None;
+        self.target = None;
+    }
+
+    pub fn run_to(&mut self, address: usize) {
+        self.target = Some(Target::Address(address));
+    }
+
+    /// Execute a single instruction, entering subroutine calls
+    pub fn step(
+        &mut self,
+        backend: &mut dyn backend::Machine,
+        keypad_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<(), backend::BackendError> {
+        let _ = backend.step(keypad_state, persistent_storage)?;
+        self.halt(backend.cpu_state().index);
+
+        Ok(())
+    }
+
+    /// Run until the current subroutine returns, returns false if there's no subroutine to return from
+    pub fn step_out(&mut self, backend: &dyn backend::Machine) -> bool {
+        let Some(depth) = backend.cpu_state().stack.len().checked_sub(1) else {
+            return false;
+        };
+
+        self.target = Some(Target::Return(depth));
+        true
+    }
+
+    /// Run until the instruction at the program counter is done, subroutine calls included
+    pub fn step_over(&mut self, backend: &dyn backend::Machine) {
+        self.target = Some(Target::Return(backend.cpu_state().stack.len()));
+    }
+
+    /// Decrement the timers and execute at most `n` instructions like [`backend::Machine::tick`],
+    /// halting at breakpoints and once the pending step is done
+    ///
+    /// Returns whether execution was halted
+    pub fn tick(
+        &mut self,
+        backend: &mut dyn backend::Machine,
+        n: u16,
+        keypad_state: &mut interfaces::KeypadState,
+        persistent_storage: &mut [u8],
+    ) -> Result<bool, backend::BackendError> {
+        if self.breakpoints.is_empty() && self.target.is_none() {
+            backend.tick(n, keypad_state, persistent_storage)?;
+            return Ok(false);
+        }
+
+        backend.begin_tick();
+
+        let mut executed = 0;
+        while !backend.is_tick_over(executed, n) {
+            let index = backend.cpu_state().index;
+            let resumed = self.halted_at.take() == Some(index);
+
+            if !resumed
+                && (self.breakpoints.contains(&index)
+                    || matches!(self.target, Some(Target::Address(address)) if address == index))
+            {
+                self.halt(index);
+                return Ok(true);
+            }
+
+            let control_flow = backend.step(keypad_state, persistent_storage)?;
+
+            let cpu_state = backend.cpu_state();
+            if matches!(self.target, Some(Target::Return(depth)) if cpu_state.stack.len() <= depth)
+            {
+                self.halt(cpu_state.index);
+                return Ok(true);
+            }
+
+            if control_flow.is_break() {
+                break;
+            }
+
+            executed += 1;
+        }
+
+        Ok(false)
+    }
+
+    pub fn toggle_breakpoint(&mut self, address: usize) {
+        if !self.breakpoints.remove(&address) {
+            self.breakpoints.insert(address);
+        }
+    }
+
+    fn halt(&mut self, index: usize) {
+        self.halted_at = Some(index);
+        self.target = None;
+    }
+}
@@ -17,24 +17,23 @@ use std::error;
 use std::fmt;
 
 use crate::backend;
+use crate::movie;
 
 #[derive(Debug)]
 pub enum FrontendError {
+    #[cfg(feature = "audio")]
     Audio(rodio::PlayError),
     Backend(backend::BackendError),
+    Movie(movie::MovieError),
 }
 
 impl FrontendError {
     pub fn is_fatal(&self) -> bool {
         match self {
-            Self::Backend(error) => matches!(
-                error.kind,
-                backend::BackendErrorKind::MemoryOverflow
-                    | backend::BackendErrorKind::ProgramInvalid
-                    | backend::BackendErrorKind::ProgramNotLoaded
-                    | backend::BackendErrorKind::DisplayNotConnected
-            ),
-            _ => true,
+            #[cfg(feature = "audio")]
+            Self::Audio(..) => true,
+            Self::Backend(error) => error.is_fatal(),
+            Self::Movie(..) => false,
         }
     }
 }
@@ -42,8 +41,10 @@ impl FrontendError {
 impl fmt::Display for FrontendError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "audio")]
             Self::Audio(error) => write!(f, "{}", error),
             Self::Backend(error) => write!(f, "{}", error),
+            Self::Movie(error) => write!(f, "{}", error),
         }
     }
 }
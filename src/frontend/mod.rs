@@ -13,22 +13,51 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::ops;
+use std::time;
+
 use eframe::egui;
 
+#[cfg(feature = "audio")]
 use rodio::source;
-use rodio::Source;
 
-use crate::backend::{self, interfaces};
+use crate::backend::{self, interfaces, state};
 use crate::defaults;
+use crate::movie;
+use crate::png;
 
+mod buzzer;
+mod capture;
+mod debugger;
 mod error;
+mod rewind;
+mod scheduler;
+mod speaker;
 
+pub use buzzer::Buzzer;
+pub use capture::Capture;
+pub use debugger::Debugger;
 pub use error::FrontendError;
+pub use rewind::Rewind;
+pub use scheduler::Scheduler;
+pub use speaker::Speaker;
+
+pub const DEFAULT_REWIND_DEPTH: u32 = 10;
+pub const DEFAULT_SPEED: u32 = 100;
+/// Speed while fast-forwarding, in percent of real time
+pub const FAST_FORWARD_SPEED: u32 = 400;
+/// Scales that screenshots can be saved at, in pixels per pixel of the display
+pub const SCREENSHOT_SCALE_RANGE: ops::RangeInclusive<u32> = 1..=16;
+/// Speeds that can be picked for slow motion, in percent of real time
+pub const SPEED_RANGE: ops::RangeInclusive<u32> = 10..=DEFAULT_SPEED;
+
+/// Keyboard keys bound to each CHIP-8 key
+pub type KeyMap = [Vec<egui::Key>; backend::KEY_COUNT];
 
-const INSTRUCTIONS_PER_TICK: u8 = 28;
-const BUZZ_FREQUENCY: f32 = 220.0;
-const BUZZ_AMPLITUDE: f32 = 10.0;
+const STATE_MAGIC: &[u8; 4] = b"RSST";
+const STATE_VERSION: u16 = 3;
 
+#[cfg(feature = "audio")]
 #[repr(transparent)]
 pub struct Beep {
     sine: source::SineWave,
@@ -38,95 +67,361 @@ pub struct Beep {
 pub struct Colors {
     pub active: egui::Color32,
     pub inactive: egui::Color32,
+    pub overlap: egui::Color32,
+    pub secondary: egui::Color32,
 }
 
 pub struct Frontend {
     pub backend: backend::Backend,
+    capture: Option<Capture>,
     pub colors: Colors,
+    pub debugger: Debugger,
     display_texture: egui::TextureHandle,
+    /// Run at [`FAST_FORWARD_SPEED`] instead of [`Frontend::speed`]
+    pub fast_forward: bool,
+    pub instructions_per_tick: u16,
+    pub key_map: KeyMap,
     keypad_state: interfaces::KeypadState,
-    sink: rodio::Sink,
-    _stream: rodio::OutputStreamHandle,
+    movie: Option<movie::Session>,
+    rewind: Rewind,
+    /// How far back in time the rewind buffer reaches, in seconds
+    pub rewind_depth: u32,
+    scheduler: Scheduler,
+    speaker: Speaker,
+    /// Speed of emulation in percent of real time, below 100 for slow motion
+    pub speed: u32,
 }
 
 impl Colors {
-    fn get(&self, pixel: bool) -> egui::Color32 {
-        match pixel {
-            true => self.active,
-            false => self.inactive,
+    fn get(&self, pixel: u8) -> egui::Color32 {
+        match pixel & 0b11 {
+            0b00 => self.inactive,
+            0b01 => self.active,
+            0b10 => self.secondary,
+            _ => self.overlap,
         }
     }
+
+    /// RGB colors of the pixels, by their value
+    pub fn palette(&self) -> [[u8; 3]; 4] {
+        [0, 1, 2, 3].map(|pixel| {
+            let color = self.get(pixel);
+            [color.r(), color.g(), color.b()]
+        })
+    }
 }
 
 impl Frontend {
+    /// Run exactly one tick while suspended, returns true if the debugger halted execution
+    pub fn advance_frame(
+        &mut self,
+        ctx: &egui::Context,
+        persistent_storage: &mut [u8],
+    ) -> Result<bool, FrontendError> {
+        let halted = self.run(ctx, persistent_storage, 1)?;
+        self.suspend();
+
+        Ok(halted)
+    }
+
+    fn current_speed(&self) -> u32 {
+        match self.fast_forward {
+            true => FAST_FORWARD_SPEED,
+            false => self.speed,
+        }
+    }
+
     #[inline]
     pub fn display_texture(&self) -> egui::TextureId {
         self.display_texture.id()
     }
 
-    pub fn new(
-        backend: backend::Backend,
-        ctx: &egui::Context,
-        stream: rodio::OutputStreamHandle,
-    ) -> Self {
-        let sink = rodio::Sink::try_new(&stream)
-            .map_err(FrontendError::Audio)
-            .unwrap();
-        sink.pause();
-        sink.append(
-            source::SineWave::new(BUZZ_FREQUENCY)
-                .stoppable()
-                .amplify(BUZZ_AMPLITUDE),
-        );
+    #[inline]
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
 
+    #[inline]
+    pub fn movie(&self) -> Option<&movie::Session> {
+        self.movie.as_ref()
+    }
+
+    pub fn new(backend: backend::Backend, ctx: &egui::Context) -> Self {
         Self {
+            capture: None,
             colors: defaults::COLORS,
+            debugger: Debugger::new(),
             display_texture: ctx.load_texture(
                 "Display Texture",
                 egui::ColorImage::new(backend.display_buffer_size(), defaults::COLORS.inactive),
                 egui::TextureOptions::default(),
             ),
+            speaker: Speaker::new(backend.audio().copied()),
             backend,
+            fast_forward: false,
+            instructions_per_tick: backend::DEFAULT_INSTRUCTIONS_PER_TICK,
+            key_map: default_key_map(),
             keypad_state: interfaces::KeypadState::new(),
-            sink,
-            _stream: stream,
+            movie: None,
+            rewind: Rewind::new(),
+            rewind_depth: DEFAULT_REWIND_DEPTH,
+            scheduler: Scheduler::new(),
+            speed: DEFAULT_SPEED,
         }
     }
 
+    /// Feed the keypad from a movie instead of the keyboard, the program has to be loaded the way
+    /// [`movie::Movie::prepare`] sets the backend up
+    pub fn play_movie(&mut self, movie: movie::Movie) {
+        if movie.frames.is_empty() {
+            return;
+        }
+
+        self.keypad_state = interfaces::KeypadState::new();
+        self.movie = Some(movie::Session::Playback {
+            frame: 0,
+            persistent_storage: movie.persistent_storage.clone(),
+            movie,
+        });
+    }
+
+    /// Record the keypad of every tick into a movie made right after loading the program
+    pub fn record_movie(&mut self, movie: movie::Movie) {
+        self.keypad_state = interfaces::KeypadState::new();
+        self.movie = Some(movie::Session::Recording(movie));
+    }
+
     pub fn reset(&mut self) {
         self.backend.reset();
-        self.sink.pause();
+        self.debugger.reset();
+        self.rewind.clear();
+        self.suspend();
+    }
+
+    /// Restore a save state made by [`Frontend::save_state`] with the same variant
+    pub fn restore_state(
+        &mut self,
+        variant: &str,
+        data: &[u8],
+        persistent_storage: &mut [u8],
+    ) -> Result<(), FrontendError> {
+        let mut reader = state::Reader::new(data);
+
+        let rpl_user_flags =
+            read_state_header(&mut reader, variant).map_err(FrontendError::Backend)?;
+        if rpl_user_flags.len() != persistent_storage.len() {
+            return Err(FrontendError::Backend(state::invalid()));
+        }
+
+        let mut backup = state::Writer::new();
+        self.backend.save_state(&mut backup);
+
+        if let Err(error) =
+            self.backend
+                .restore_state(&mut reader)
+                .and_then(|_| match reader.is_empty() {
+                    true => Ok(()),
+                    false => Err(state::invalid()),
+                })
+        {
+            let backup = backup.into_inner();
+            self.backend
+                .restore_state(&mut state::Reader::new(&backup))
+                .expect("a snapshot of the current state should always be restorable");
+
+            return Err(FrontendError::Backend(error));
+        }
+
+        persistent_storage.copy_from_slice(rpl_user_flags);
+        self.debugger.reset();
+        self.rewind.clear();
+        self.update_texture()
     }
 
-    pub fn suspend(&self) {
-        self.sink.pause()
+    /// Step the machine back in time by the ticks that are due since the last call, returns false
+    /// once the rewind buffer is exhausted
+    pub fn rewind(&mut self) -> Result<bool, FrontendError> {
+        self.speaker.pause();
+
+        let ticks = self
+            .scheduler
+            .advance(time::Instant::now(), self.current_speed());
+        if ticks == 0 {
+            return Ok(true);
+        }
+
+        let Some(snapshot) = self.rewind.pop(ticks as usize) else {
+            return Ok(false);
+        };
+
+        self.backend
+            .restore_state(&mut state::Reader::new(snapshot))
+            .map_err(FrontendError::Backend)?;
+        self.debugger.reset();
+        self.update_texture()?;
+
+        Ok(true)
     }
 
-    pub fn tick(
+    /// Run `ticks` ticks, returns true if the debugger halted execution
+    fn run(
         &mut self,
         ctx: &egui::Context,
         persistent_storage: &mut [u8],
-    ) -> Result<(), FrontendError> {
-        match self.backend.get_timers().sound {
-            0 => self.sink.pause(),
-            _ => self.sink.play(),
-        }
+        ticks: u32,
+    ) -> Result<bool, FrontendError> {
+        let instructions_per_tick = self
+            .movie
+            .as_ref()
+            .map_or(self.instructions_per_tick, |session| {
+                session.movie().instructions_per_tick
+            });
 
-        ctx.input(|input| {
-            self.keypad_state.update(input);
-        });
+        let mut finished = false;
+        let mut halted = false;
+        // capturing the display marks it as clean before the texture is updated
+        let mut texture_stale = false;
+
+        for _ in 0..ticks {
+            let persistent_storage = match self.movie {
+                Some(movie::Session::Playback {
+                    ref mut frame,
+                    ref movie,
+                    ref mut persistent_storage,
+                }) => {
+                    self.keypad_state.advance();
+                    self.keypad_state.set_held(movie.frames[*frame]);
+
+                    *frame += 1;
+                    finished = *frame == movie.frames.len();
+
+                    persistent_storage.as_mut_slice()
+                }
+                _ => {
+                    // a key release is only seen by the first tick when the keypad is updated every tick
+                    self.update_keypad(ctx);
+
+                    if let Some(movie::Session::Recording(ref mut movie)) = self.movie {
+                        movie.frames.push(self.keypad_state.held());
+                    }
 
-        match self.backend.tick(
-            INSTRUCTIONS_PER_TICK,
-            &mut self.keypad_state,
-            Some(persistent_storage),
-        ) {
-            Ok(_) => (),
-            Err(error) => {
-                return Err(FrontendError::Backend(error));
+                    &mut *persistent_storage
+                }
+            };
+
+            halted = self
+                .debugger
+                .tick(
+                    self.backend.as_mut(),
+                    instructions_per_tick,
+                    &mut self.keypad_state,
+                    persistent_storage,
+                )
+                .map_err(FrontendError::Backend)?;
+
+            if let Some(ref mut capture) = self.capture {
+                texture_stale |= capture
+                    .push(self.backend.as_mut())
+                    .map_err(FrontendError::Backend)?;
             }
+
+            if self.rewind_depth > 0 {
+                let mut snapshot = state::Writer::new();
+                self.backend.save_state(&mut snapshot);
+                self.rewind.push(
+                    snapshot.into_inner(),
+                    self.rewind_depth as usize * backend::TICKS_PER_SECOND,
+                );
+            }
+
+            if finished || halted || self.backend.has_program_exited() {
+                break;
+            }
+        }
+
+        self.speaker.set_audio(self.backend.audio().copied());
+
+        match self.backend.timers().sound {
+            _ if halted => self.suspend(),
+            0 => self.speaker.pause(),
+            _ => self.speaker.play(),
+        }
+
+        if texture_stale || self.backend.is_display_buffer_dirty() {
+            self.update_texture()?;
+        }
+
+        // the keyboard takes over once the playback is over
+        if let Some(session) = self.movie.take_if(|_| finished) {
+            session
+                .movie()
+                .verify(self.backend.as_mut())
+                .map_err(FrontendError::Movie)?;
+        }
+
+        Ok(halted)
+    }
+
+    /// Serialize the state of the machine along with the RPL user flags
+    pub fn save_state(&self, variant: &str, persistent_storage: &[u8]) -> Vec<u8> {
+        let mut writer = state::Writer::new();
+
+        write_state_header(&mut writer, variant, persistent_storage);
+        self.backend.save_state(&mut writer);
+
+        writer.into_inner()
+    }
+
+    /// Encode the display as a PNG in the current colors, with every pixel scaled up to `scale` by
+    /// `scale` pixels
+    pub fn screenshot(&mut self, scale: usize) -> Result<Vec<u8>, FrontendError> {
+        // reading the display marks it as clean, the texture mustn't miss the changes
+        if self.backend.is_display_buffer_dirty() {
+            self.update_texture()?;
+        }
+
+        let [width, height] = self.backend.display_buffer_size();
+        let pixels: Vec<u8> = self
+            .backend
+            .display_buffer()
+            .map_err(FrontendError::Backend)?
+            .map(|pixel| pixel & 0b11)
+            .collect();
+
+        Ok(png::encode(
+            width * scale,
+            height * scale,
+            &png::scale(width, &pixels, scale),
+            &self.colors.palette(),
+        ))
+    }
+
+    /// Start capturing the display and the buzzer of every tick
+    pub fn start_capture(&mut self) -> Result<(), FrontendError> {
+        if self.backend.is_display_buffer_dirty() {
+            self.update_texture()?;
         }
 
+        self.capture = Some(Capture::new(self.backend.as_mut()).map_err(FrontendError::Backend)?);
+        Ok(())
+    }
+
+    /// Execute a single instruction through the debugger while suspended
+    pub fn step(
+        &mut self,
+        ctx: &egui::Context,
+        persistent_storage: &mut [u8],
+    ) -> Result<(), FrontendError> {
+        self.update_keypad(ctx);
+
+        self.debugger
+            .step(
+                self.backend.as_mut(),
+                &mut self.keypad_state,
+                persistent_storage,
+            )
+            .map_err(FrontendError::Backend)?;
+
         if self.backend.is_display_buffer_dirty() {
             self.update_texture()?;
         }
@@ -134,10 +429,76 @@ impl Frontend {
         Ok(())
     }
 
+    /// Step over the instruction at the program counter, running the whole subroutine if it's a call
+    pub fn step_over(
+        &mut self,
+        ctx: &egui::Context,
+        persistent_storage: &mut [u8],
+    ) -> Result<(), FrontendError> {
+        let cpu_state = self.backend.cpu_state();
+
+        match cpu_state.memory.get(cpu_state.index) {
+            Some(byte) if byte >> 4 == 0x2 => {
+                self.debugger.step_over(self.backend.as_ref());
+                Ok(())
+            }
+            _ => self.step(ctx, persistent_storage),
+        }
+    }
+
+    #[inline]
+    pub fn stop_capture(&mut self) -> Option<Capture> {
+        self.capture.take()
+    }
+
+    /// Detach the movie, a recording is returned along with the hash of how it ended
+    pub fn stop_movie(&mut self) -> Option<movie::Movie> {
+        match self.movie.take()? {
+            movie::Session::Playback { .. } => None,
+            movie::Session::Recording(mut movie) => {
+                movie.result_hash = movie::hash(self.backend.as_mut()).ok();
+                Some(movie)
+            }
+        }
+    }
+
+    /// Pause the audio and the clock
+    pub fn suspend(&mut self) {
+        self.scheduler.pause();
+        self.speaker.pause()
+    }
+
+    /// Run the ticks that are due since the last call, returns true if the debugger halted execution
+    pub fn tick(
+        &mut self,
+        ctx: &egui::Context,
+        persistent_storage: &mut [u8],
+    ) -> Result<bool, FrontendError> {
+        let ticks = self
+            .scheduler
+            .advance(time::Instant::now(), self.current_speed());
+
+        self.run(ctx, persistent_storage, ticks)
+    }
+
+    /// Feed the keyboard to the keypad through the key map
+    fn update_keypad(&mut self, ctx: &egui::Context) {
+        self.keypad_state.advance();
+
+        ctx.input(|input| {
+            for (key, keys) in self.key_map.iter().enumerate() {
+                match keys.iter().any(|&key| input.key_down(key)) {
+                    true => self.keypad_state.press(key),
+                    false => self.keypad_state.release(key),
+                }
+            }
+        });
+    }
+
     pub fn update_texture(&mut self) -> Result<(), FrontendError> {
         let pixels: Vec<egui::Color32> = self
             .backend
-            .get_display_buffer()
+            .display_buffer()
             .map_err(|error| FrontendError::Backend(error))?
             .map(|pixel| self.colors.get(pixel))
             .collect();
@@ -153,3 +514,65 @@ impl Frontend {
         Ok(())
     }
 }
+
+pub fn default_key_map() -> KeyMap {
+    defaults::KEY_MAP.map(|key| vec![key])
+}
+
+/// Check the header of a save state made with `variant` and read the RPL user flags that follow it
+fn read_state_header<'a>(
+    reader: &mut state::Reader<'a>,
+    variant: &str,
+) -> Result<&'a [u8], backend::BackendError> {
+    if reader.read_exact(STATE_MAGIC.len()).ok() != Some(STATE_MAGIC)
+        || reader.read_u16().ok() != Some(STATE_VERSION)
+        || reader.read_bytes().ok() != Some(variant.as_bytes())
+    {
+        return Err(state::invalid());
+    }
+
+    reader.read_bytes()
+}
+
+fn write_state_header(writer: &mut state::Writer, variant: &str, persistent_storage: &[u8]) {
+    writer.write_exact(STATE_MAGIC);
+    writer.write_u16(STATE_VERSION);
+    writer.write_bytes(variant.as_bytes());
+    writer.write_bytes(persistent_storage);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(magic: &[u8], version: u16, variant: &str) -> Vec<u8> {
+        let mut writer = state::Writer::new();
+        writer.write_exact(magic);
+        writer.write_u16(version);
+        writer.write_bytes(variant.as_bytes());
+        writer.write_bytes(&[1, 2, 3]);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn state_header() {
+        let mut writer = state::Writer::new();
+        write_state_header(&mut writer, "schip", &[1, 2, 3]);
+        let data = writer.into_inner();
+        assert_eq!(data, header(STATE_MAGIC, STATE_VERSION, "schip"));
+
+        let mut reader = state::Reader::new(&data);
+        assert_eq!(read_state_header(&mut reader, "schip").unwrap(), [1, 2, 3]);
+        assert!(reader.is_empty());
+
+        for data in [
+            header(b"RSSX", STATE_VERSION, "schip"),
+            header(STATE_MAGIC, STATE_VERSION - 1, "schip"),
+            header(STATE_MAGIC, STATE_VERSION + 1, "schip"),
+            header(STATE_MAGIC, STATE_VERSION, "chip8"),
+            data[..data.len() - 1].to_vec(),
+        ] {
+            assert!(read_state_header(&mut state::Reader::new(&data), "schip").is_err());
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+
+// unchanged runs shorter than this don't split a span, the offset would cost more than the bytes
+const SPAN_MERGE_DISTANCE: usize = 8;
+
+/// A patch that turns a snapshot into the one taken right before it
+struct Delta {
+    length: usize,
+    spans: Vec<(usize, Vec<u8>)>,
+}
+
+/// A ring buffer of machine snapshots
+///
+/// Only the newest snapshot is kept whole, every older one is stored as the changed byte spans
+/// (memory, display rows, registers) relative to its successor
+#[derive(Default)]
+pub struct Rewind {
+    deltas: VecDeque<Delta>,
+    latest: Option<Vec<u8>>,
+}
+
+impl Delta {
+    fn apply(&self, data: &mut Vec<u8>) {
+        data.resize(self.length, 0);
+
+        for (offset, bytes) in self.spans.iter() {
+            data[*offset..*offset + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+
+    fn new(from: &[u8], to: &[u8]) -> Self {
+        let mut spans: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut last_end = 0;
+
+        for i in (0..to.len()).filter(|&i| from.get(i) != Some(&to[i])) {
+            match spans.last_mut() {
+                Some((offset, bytes)) if i - last_end < SPAN_MERGE_DISTANCE => {
+                    bytes.extend_from_slice(&to[*offset + bytes.len()..=i]);
+                }
+                _ => spans.push((i, vec![to[i]])),
+            }
+
+            last_end = i + 1;
+        }
+
+        Self {
+            length: to.len(),
+            spans,
+        }
+    }
+}
+
+impl Rewind {
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.latest = None;
+    }
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Step back `n` snapshots, or as many as there are, returns `None` if there's none left
+    pub fn pop(&mut self, n: usize) -> Option<&[u8]> {
+        let latest = self.latest.as_mut()?;
+
+        if self.deltas.is_empty() {
+            return None;
+        }
+
+        let start = self.deltas.len().saturating_sub(n);
+        for delta in self.deltas.drain(start..).rev() {
+            delta.apply(latest);
+        }
+
+        Some(latest)
+    }
+
+    /// Record a snapshot, forgetting the oldest ones if there are more than `capacity`
+    pub fn push(&mut self, snapshot: Vec<u8>, capacity: usize) {
+        if let Some(latest) = self.latest.take() {
+            self.deltas.push_back(Delta::new(&snapshot, &latest));
+        }
+
+        while self.deltas.len() > capacity {
+            self.deltas.pop_front();
+        }
+
+        self.latest = Some(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{self, interfaces, state};
+
+    #[test]
+    fn exact_snapshots() {
+        let mut backend = (backend::VARIANTS[0].new)();
+        // draws random sprites all over the display
+        backend
+            .load(
+                None,
+                &[0xC0, 0x3F, 0xC1, 0x1F, 0xA2, 0x00, 0xD0, 0x15, 0x12, 0x00],
+            )
+            .unwrap();
+
+        let mut rewind = Rewind::new();
+        let mut snapshots = Vec::new();
+
+        for _ in 0..20 {
+            backend
+                .tick(8, &mut interfaces::KeypadState::new(), &mut [])
+                .unwrap();
+
+            let mut writer = state::Writer::new();
+            backend.save_state(&mut writer);
+            snapshots.push(writer.into_inner());
+            rewind.push(snapshots[snapshots.len() - 1].clone(), 100);
+        }
+
+        assert_eq!(rewind.pop(1).unwrap(), snapshots[18]);
+        assert_eq!(rewind.pop(3).unwrap(), snapshots[15]);
+
+        let snapshot = rewind.pop(5).unwrap();
+        assert_eq!(snapshot, snapshots[10]);
+        backend
+            .restore_state(&mut state::Reader::new(snapshot))
+            .unwrap();
+    }
+
+    #[test]
+    fn different_lengths() {
+        let mut rewind = Rewind::new();
+
+        for snapshot in [vec![1; 10], vec![2; 30], vec![3; 5], vec![4; 20]] {
+            rewind.push(snapshot, 10);
+        }
+
+        assert_eq!(rewind.pop(1).unwrap(), [3; 5]);
+        assert_eq!(rewind.pop(1).unwrap(), [2; 30]);
+        assert_eq!(rewind.pop(1).unwrap(), [1; 10]);
+    }
+
+    #[test]
+    fn capacity() {
+        let mut rewind = Rewind::new();
+        assert!(rewind.pop(1).is_none());
+
+        for i in 0..10 {
+            rewind.push(vec![i; 4], 4);
+        }
+
+        // only the 4 snapshots before the latest one are left
+        assert_eq!(rewind.pop(usize::MAX).unwrap(), [5; 4]);
+        assert!(rewind.pop(1).is_none());
+    }
+}
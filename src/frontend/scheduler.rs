@@ -0,0 +1,126 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time;
+
+use crate::backend::TICKS_PER_SECOND;
+
+/// Most ticks run to catch up in a single update, the rest of the backlog is dropped
+pub const MAX_CATCH_UP_TICKS: u32 = 10;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Turns wall-clock time into 60 Hz ticks, independent of how often it's polled
+#[derive(Default)]
+pub struct Scheduler {
+    /// Time owed, in nanoseconds times the tick rate so that a tick is exactly a second's worth
+    debt: u128,
+    last_update: Option<time::Instant>,
+}
+
+impl Scheduler {
+    /// Account for the time passed since the last call at `speed` percent of real time, returns the
+    /// number of ticks that are due
+    ///
+    /// The first call after creation or [`Scheduler::pause`] only starts the clock. If more than
+    /// [`MAX_CATCH_UP_TICKS`] (scaled up along with the speed) are due, that many are returned and
+    /// the rest are dropped
+    pub fn advance(&mut self, now: time::Instant, speed: u32) -> u32 {
+        let Some(last_update) = self.last_update.replace(now) else {
+            return 0;
+        };
+
+        self.debt += now.saturating_duration_since(last_update).as_nanos()
+            * TICKS_PER_SECOND as u128
+            * speed as u128
+            / 100;
+
+        let max_ticks = MAX_CATCH_UP_TICKS * speed.max(100) / 100;
+        let ticks = self.debt / NANOS_PER_SECOND;
+        if ticks > max_ticks as u128 {
+            self.debt = 0;
+            return max_ticks;
+        }
+
+        self.debt %= NANOS_PER_SECOND;
+        ticks as u32
+    }
+
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Stop the clock, the time until the next [`Scheduler::advance`] isn't owed
+    pub fn pause(&mut self) {
+        self.debt = 0;
+        self.last_update = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks due over a second polled every `interval` milliseconds
+    fn ticks_per_second(speed: u32, interval: u64) -> u32 {
+        let mut scheduler = Scheduler::new();
+        let start = time::Instant::now();
+        assert_eq!(scheduler.advance(start, speed), 0);
+
+        (1..=1000 / interval)
+            .map(|i| scheduler.advance(start + time::Duration::from_millis(i * interval), speed))
+            .sum()
+    }
+
+    #[test]
+    fn elapsed_time() {
+        for interval in [1, 4, 8, 25, 100] {
+            assert_eq!(ticks_per_second(100, interval), 60, "{} ms", interval);
+        }
+
+        assert_eq!(ticks_per_second(50, 4), 30);
+        assert_eq!(ticks_per_second(10, 8), 6);
+        assert_eq!(ticks_per_second(200, 4), 120);
+    }
+
+    #[test]
+    fn catch_up_cap() {
+        let mut scheduler = Scheduler::new();
+        let start = time::Instant::now();
+        let second = time::Duration::from_secs(1);
+
+        scheduler.advance(start, 100);
+        assert_eq!(scheduler.advance(start + second, 100), MAX_CATCH_UP_TICKS);
+        // the rest of the backlog was dropped
+        assert_eq!(
+            scheduler.advance(start + second + time::Duration::from_millis(10), 100),
+            0
+        );
+
+        assert_eq!(
+            scheduler.advance(start + 3 * second, 400),
+            MAX_CATCH_UP_TICKS * 4
+        );
+
+        // the time spent paused isn't owed
+        scheduler.pause();
+        assert_eq!(scheduler.advance(start + 10 * second, 100), 0);
+        assert_eq!(
+            scheduler.advance(start + 10 * second + time::Duration::from_millis(50), 100),
+            3
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#[cfg(feature = "audio")]
+use std::sync;
+
+#[cfg(feature = "audio")]
+use rodio::Source;
+
+use crate::backend::xochip;
+
+#[cfg(feature = "audio")]
+use super::{Buzzer, FrontendError};
+
+#[cfg(feature = "audio")]
+const BUZZ_AMPLITUDE: f32 = 10.0;
+pub(super) const BUZZ_FREQUENCY: f32 = 220.0;
+
+/// Plays the buzzer on the default output device
+#[cfg(feature = "audio")]
+pub struct Speaker {
+    audio: sync::Arc<sync::Mutex<Option<xochip::Audio>>>,
+    sink: rodio::Sink,
+    _stream: rodio::OutputStream,
+}
+
+/// Stands in for the speaker when built without the `audio` feature, stays silent
+#[cfg(not(feature = "audio"))]
+pub struct Speaker;
+
+#[cfg(feature = "audio")]
+impl Speaker {
+    pub fn new(audio: Option<xochip::Audio>) -> Self {
+        let (stream, handle) = rodio::OutputStream::try_default().unwrap();
+        let sink = rodio::Sink::try_new(&handle)
+            .map_err(FrontendError::Audio)
+            .unwrap();
+        let audio = sync::Arc::new(sync::Mutex::new(audio));
+
+        sink.pause();
+        sink.append(
+            Buzzer::new(BUZZ_FREQUENCY, audio.clone())
+                .stoppable()
+                .amplify(BUZZ_AMPLITUDE),
+        );
+
+        Self {
+            audio,
+            sink,
+            _stream: stream,
+        }
+    }
+
+    #[inline]
+    pub fn pause(&self) {
+        self.sink.pause()
+    }
+
+    #[inline]
+    pub fn play(&self) {
+        self.sink.play()
+    }
+
+    /// Play an XO-CHIP audio pattern instead of the sine wave
+    pub fn set_audio(&self, audio: Option<xochip::Audio>) {
+        *self.audio.lock().unwrap() = audio;
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl Speaker {
+    pub fn new(_audio: Option<xochip::Audio>) -> Self {
+        Self
+    }
+
+    #[inline]
+    pub fn pause(&self) {}
+
+    #[inline]
+    pub fn play(&self) {}
+
+    #[inline]
+    pub fn set_audio(&self, _audio: Option<xochip::Audio>) {}
+}
@@ -0,0 +1,264 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use crate::bits::BitWriter;
+
+const MAX_CODE_WIDTH: u32 = 12;
+const SUB_BLOCK_SIZE: usize = 255;
+
+/// Encode frames of palette indices as an animated GIF that loops forever
+///
+/// Every frame is the delay in hundredths of a second before the next one and the indices row by
+/// row, `palette` has at most 256 colors
+pub fn encode<'a>(
+    width: usize,
+    height: usize,
+    palette: &[[u8; 3]],
+    frames: impl IntoIterator<Item = (u16, &'a [u8])>,
+) -> Vec<u8> {
+    // bits per index, the table of a GIF has a power of two colors
+    let depth = palette.len().next_power_of_two().trailing_zeros().max(1);
+
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend_from_slice(&(width as u16).to_le_bytes());
+    gif.extend_from_slice(&(height as u16).to_le_bytes());
+    // a global color table of 2^depth colors at a color resolution of depth bits
+    gif.extend_from_slice(&[0x80 | (depth as u8 - 1) << 4 | (depth as u8 - 1), 0, 0]);
+
+    for i in 0..1 << depth {
+        gif.extend_from_slice(&palette.get(i).copied().unwrap_or_default());
+    }
+
+    // the NETSCAPE2.0 extension, repeat forever
+    gif.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for (delay, pixels) in frames {
+        // graphic control extension, the frame isn't disposed of and nothing is transparent
+        gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+        gif.extend_from_slice(&delay.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        // image descriptor, the frame covers the whole screen
+        gif.extend_from_slice(&[0x2C, 0, 0, 0, 0]);
+        gif.extend_from_slice(&(width as u16).to_le_bytes());
+        gif.extend_from_slice(&(height as u16).to_le_bytes());
+        gif.push(0);
+
+        let min_code_width = depth.max(2);
+        gif.push(min_code_width as u8);
+
+        for block in lzw(pixels, min_code_width).chunks(SUB_BLOCK_SIZE) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3B);
+    gif
+}
+
+/// Compress indices with the variable width LZW codes of GIF
+fn lzw(pixels: &[u8], min_code_width: u32) -> Vec<u8> {
+    let clear_code = 1u32 << min_code_width;
+    let end_code = clear_code + 1;
+
+    let mut writer = BitWriter::default();
+    let mut code_width = min_code_width + 1;
+    let mut next_code = end_code + 1;
+    let mut table: HashMap<(u32, u8), u32> = HashMap::new();
+
+    writer.write_bits(clear_code, code_width);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        writer.write_bits(end_code, code_width);
+        return writer.finish();
+    };
+
+    let mut prefix = first as u32;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write_bits(prefix, code_width);
+
+        if next_code == 1 << MAX_CODE_WIDTH {
+            // the table is full, start over
+            writer.write_bits(clear_code, code_width);
+            table.clear();
+            code_width = min_code_width + 1;
+            next_code = end_code + 1;
+        } else {
+            // the codes get wider as soon as the next one doesn't fit
+            if next_code >= 1 << code_width {
+                code_width += 1;
+            }
+
+            table.insert((prefix, pixel), next_code);
+            next_code += 1;
+        }
+
+        prefix = pixel as u32;
+    }
+
+    writer.write_bits(prefix, code_width);
+    if next_code >= 1 << code_width && code_width < MAX_CODE_WIDTH {
+        code_width += 1;
+    }
+    writer.write_bits(end_code, code_width);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompress the LZW codes of a frame, returns the indices and how many clear codes there are
+    fn decode(data: &[u8], min_code_width: u32) -> (Vec<u8>, usize) {
+        let clear_code = 1 << min_code_width;
+        let end_code = clear_code + 1;
+
+        let mut bits = data
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i & 1) as usize));
+        let mut read = |width: u32| {
+            (0..width).fold(0, |code, i| code | bits.next().expect("an end code") << i)
+        };
+
+        let mut clears = 0;
+        let mut code_width = min_code_width + 1;
+        let mut pixels = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+
+        loop {
+            let code = read(code_width);
+
+            if code == clear_code {
+                clears += 1;
+                code_width = min_code_width + 1;
+                previous = None;
+                table = (0..clear_code).map(|index| vec![index as u8]).collect();
+                // the clear and end codes
+                table.extend([Vec::new(), Vec::new()]);
+                continue;
+            }
+
+            if code == end_code {
+                break;
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                // the code that's about to be added, the previous string and its first index
+                (None, Some(previous)) if code == table.len() => {
+                    [previous.as_slice(), &previous[..1]].concat()
+                }
+                _ => panic!("code {} isn't in the table", code),
+            };
+
+            if let Some(mut string) = previous.take() {
+                if table.len() < 1 << MAX_CODE_WIDTH {
+                    string.push(entry[0]);
+                    table.push(string);
+                }
+            }
+
+            if table.len() == 1 << code_width && code_width < MAX_CODE_WIDTH {
+                code_width += 1;
+            }
+
+            pixels.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+
+        assert!(bits.all(|bit| bit == 0), "only padding after the end code");
+        (pixels, clears)
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut seed = 1u32;
+        let noise: Vec<u8> = (0..20000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8 & 0xF
+            })
+            .collect();
+        let stripes: Vec<u8> = (0..64 * 32).map(|i| (i / 3 % 2) as u8).collect();
+
+        for (pixels, min_code_width) in [
+            (&[][..], 2),
+            (&[1], 2),
+            (&[3, 3, 3, 3, 3, 3, 3], 2),
+            (&stripes, 2),
+            (&noise, 4),
+            (&noise, 8),
+        ] {
+            let (decoded, clears) = decode(&lzw(pixels, min_code_width), min_code_width);
+            assert_eq!(decoded, pixels);
+            assert!(clears >= 1);
+        }
+    }
+
+    #[test]
+    fn lzw_table_reset() {
+        // every pair of indices is new for a long time, so the table fills up and starts over
+        let mut seed = 7u32;
+        let pixels: Vec<u8> = (0..50000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+
+        let (decoded, clears) = decode(&lzw(&pixels, 8), 8);
+        assert_eq!(decoded, pixels);
+        assert!(clears > 2, "{} clear codes", clears);
+    }
+
+    #[test]
+    fn structure() {
+        let pixels = [0, 1, 1, 0];
+        let gif = encode(
+            2,
+            2,
+            &[[0, 0, 0], [0xFF, 0, 0]],
+            [(5, &pixels[..]), (5, &pixels)],
+        );
+
+        assert_eq!(gif[..6], *b"GIF89a");
+        assert_eq!(gif[6..13], [2, 0, 2, 0, 0x80, 0, 0]);
+        assert_eq!(gif[13..19], [0, 0, 0, 0xFF, 0, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+
+        // the image data of the first frame follows its graphic control extension and descriptor
+        let start = 19 + 19 + 8 + 10;
+        assert_eq!(gif[start - 10], 0x2C);
+        assert_eq!(gif[start], 2);
+
+        let length = gif[start + 1] as usize;
+        let data = &gif[start + 2..start + 2 + length];
+        assert_eq!(gif[start + 2 + length], 0);
+        assert_eq!(decode(data, 2).0, pixels);
+    }
+}
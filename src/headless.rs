@@ -0,0 +1,135 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::backend::{self, interfaces};
+use crate::movie;
+use crate::png;
+
+/// Colors of the pixels in screenshots by their value, the same as the default colors of the GUI
+pub const PALETTE: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x00],
+    [0x81, 0x5B, 0xA4],
+    [0xFF, 0xFF, 0xFF],
+    [0x4A, 0x2B, 0x5E],
+];
+const PIXEL_CHARACTERS: [char; 4] = ['.', '#', '+', '@'];
+
+/// Runs a backend without any display, audio or input
+pub struct Headless {
+    pub backend: backend::Backend,
+    keypad_state: interfaces::KeypadState,
+    persistent_storage: [u8; backend::PERSISTENT_STORAGE_SIZE],
+}
+
+impl Headless {
+    pub fn new(backend: backend::Backend) -> Self {
+        Self {
+            backend,
+            keypad_state: interfaces::KeypadState::new(),
+            persistent_storage: [0; backend::PERSISTENT_STORAGE_SIZE],
+        }
+    }
+
+    /// Play a movie back from the start of the program, stopping early if the program exits
+    pub fn play(&mut self, movie: &movie::Movie) -> Result<(), backend::BackendError> {
+        self.keypad_state = interfaces::KeypadState::new();
+        self.persistent_storage
+            .copy_from_slice(&movie.persistent_storage);
+
+        for &keys in &movie.frames {
+            self.keypad_state.advance();
+            self.keypad_state.set_held(keys);
+
+            if self.tick(movie.instructions_per_tick)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the display as text, one line per row
+    pub fn render(&mut self) -> Result<String, backend::BackendError> {
+        let [width, height] = self.backend.display_buffer_size();
+        let mut text = String::with_capacity((width + 1) * height);
+
+        for (i, pixel) in self.backend.display_buffer()?.enumerate() {
+            text.push(PIXEL_CHARACTERS[pixel as usize & 0b11]);
+
+            if (i + 1) % width == 0 {
+                text.push('\n');
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Encode the display as a PNG in the colors of `palette`, with every pixel scaled up to
+    /// `scale` by `scale` pixels
+    pub fn screenshot(
+        &mut self,
+        scale: usize,
+        palette: &[[u8; 3]; 4],
+    ) -> Result<Vec<u8>, backend::BackendError> {
+        let [width, height] = self.backend.display_buffer_size();
+        let pixels: Vec<u8> = self
+            .backend
+            .display_buffer()?
+            .map(|pixel| pixel & 0b11)
+            .collect();
+
+        Ok(png::encode(
+            width * scale,
+            height * scale,
+            &png::scale(width, &pixels, scale),
+            palette,
+        ))
+    }
+
+    /// Run `ticks` ticks of `instructions_per_tick` instructions each, stopping early if the
+    /// program exits
+    pub fn run(
+        &mut self,
+        ticks: u64,
+        instructions_per_tick: u16,
+    ) -> Result<(), backend::BackendError> {
+        for _ in 0..ticks {
+            if self.tick(instructions_per_tick)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a single tick, returns true if the program exited
+    ///
+    /// Non-fatal errors are reported to stderr and skipped over, like the GUI does
+    fn tick(&mut self, instructions_per_tick: u16) -> Result<bool, backend::BackendError> {
+        if let Err(error) = self.backend.tick(
+            instructions_per_tick,
+            &mut self.keypad_state,
+            &mut self.persistent_storage,
+        ) {
+            if error.is_fatal() {
+                return Err(error);
+            }
+
+            eprintln!("{}", error);
+        }
+
+        Ok(self.backend.has_program_exited())
+    }
+}
@@ -1,4 +1,18 @@
 pub mod backend;
+mod bits;
+#[cfg(feature = "gui")]
+pub mod config;
+#[cfg(feature = "gui")]
+mod database;
+#[cfg(feature = "gui")]
 mod defaults;
+#[cfg(feature = "gui")]
 pub mod frontend;
+pub mod gif;
+pub mod headless;
+pub mod movie;
+pub mod png;
+pub mod sha1;
+#[cfg(feature = "gui")]
 pub mod ui;
+pub mod wav;
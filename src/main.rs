@@ -13,31 +13,166 @@
 //    You should have received a copy of the GNU General Public License
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "gui")]
 const ICON_PNG: &[u8] = include_bytes!("../assets/icon.png");
 
+#[cfg(feature = "gui")]
 use std::cell;
+use std::env;
 use std::error;
 use std::fs;
-use std::io::Read;
+#[cfg(feature = "gui")]
+use std::io::{self, Read};
+#[cfg(feature = "gui")]
+use std::path;
+use std::process;
+#[cfg(feature = "gui")]
 use std::rc;
 
+use ruschip::backend::{self, disassembler};
+#[cfg(feature = "gui")]
+use ruschip::config;
+use ruschip::movie;
+
+mod cli;
+
 fn main() -> Result<(), Box<dyn error::Error>> {
-    let data_dir = dirs::data_dir()
+    let arguments = match cli::parse(env::args().skip(1)) {
+        Ok(cli::Command::Run(arguments)) => *arguments,
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(cli::Command::Version) => {
+            println!("ruschip {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if !arguments.disassemble && !arguments.headless {
+        return run_gui(arguments);
+    }
+
+    let variant = arguments.variant.or_else(|| {
+        let program = fs::read(arguments.program.as_ref()?).ok()?;
+        let id = backend::detect_variant(&program)?;
+
+        backend::VARIANTS
+            .iter()
+            .position(|variant| variant.id == id)
+    });
+
+    let mut backend = (backend::VARIANTS[variant.unwrap_or(0)].new)();
+    arguments.quirks.apply(backend.as_mut());
+
+    if arguments.disassemble {
+        let program = fs::read(arguments.program.as_ref().unwrap())?;
+
+        print!(
+            "{}",
+            disassembler::disassemble(
+                &program,
+                backend::PROGRAM_ADDRESS,
+                backend.instruction_set(),
+                arguments.syntax,
+            )
+        );
+
+        return Ok(());
+    }
+
+    run_headless(arguments, backend)
+}
+
+#[cfg(feature = "gui")]
+fn data_dir() -> path::PathBuf {
+    dirs::data_dir()
         .or(dirs::data_dir())
         .expect("couldn't find a data directory")
-        .join("ruschip");
+        .join("ruschip")
+}
+
+/// Load the config file, falling back to the defaults if it doesn't exist or is invalid
+#[cfg(feature = "gui")]
+fn load_config(path: &path::Path) -> config::Config {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Default::default(),
+        Err(error) => {
+            eprintln!("couldn't read the config, {}", error);
+            return Default::default();
+        }
+    };
+
+    config::Config::parse(&text).unwrap_or_else(|error| {
+        eprintln!("couldn't load the config, {}", error);
+        Default::default()
+    })
+}
+
+#[cfg(feature = "gui")]
+fn run_gui(arguments: cli::Arguments) -> Result<(), Box<dyn error::Error>> {
+    let data_dir = data_dir();
     let data_file = data_dir.join("rpl_user_flags.dat");
 
+    let mut config = load_config(&data_dir.join(config::FILE_NAME));
+
+    let config_variant = config.variant.as_ref().and_then(|id| {
+        backend::VARIANTS
+            .iter()
+            .position(|variant| variant.id == id)
+    });
+    let variant = arguments.variant.or(config_variant).unwrap_or(0);
+
+    let mut backend = (backend::VARIANTS[variant].new)();
+    if config_variant == Some(variant) {
+        config.quirks.apply(backend.as_mut());
+    }
+    arguments.quirks.apply(backend.as_mut());
+
     fs::create_dir_all(&data_dir)?;
 
-    let mut rpl_user_flags = [0; ruschip::backend::superchip::PERSISTENT_STORAGE_SIZE];
+    let mut rpl_user_flags = [0; backend::PERSISTENT_STORAGE_SIZE];
     let _ = fs::File::open(&data_file).and_then(|mut file| file.read(&mut rpl_user_flags));
 
     let persistent_storage = rc::Rc::new(cell::RefCell::new(rpl_user_flags));
     let persistent_storage_clone = persistent_storage.clone();
+    let data_dir_clone = data_dir.clone();
+
+    if arguments.font.is_some() {
+        config.font_path = arguments.font.clone();
+    }
+    if arguments.ips.is_some() {
+        config.instructions_per_tick = arguments.instructions_per_tick();
+    }
+    if arguments.program.is_some() {
+        config.program_path = arguments.program.clone();
+    }
+    if arguments.screenshot_directory.is_some() {
+        config.screenshot_directory = arguments.screenshot_directory.clone();
+    }
+    if let Some(scale) = arguments.screenshot_scale {
+        config.screenshot_scale = scale;
+    }
+    if arguments.seed.is_some() {
+        config.seed = arguments.seed;
+    }
+
+    let settings = ruschip::ui::Settings {
+        backend,
+        config,
+        play_movie: arguments.play,
+        record_movie: arguments.record,
+        start: arguments.program.is_some(),
+        variant,
+    };
 
     eframe::run_native(
-        "Ruschip",
+        ruschip::ui::WINDOW_TITLE,
         eframe::NativeOptions {
             drag_and_drop_support: false,
             icon_data: Some(eframe::IconData::try_from_png_bytes(ICON_PNG)?),
@@ -46,8 +181,10 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         Box::new(move |cc| {
             Box::new(ruschip::ui::App::new(
                 cc,
-                ruschip::backend::Backend::default(),
+                backend::VARIANTS.to_vec(),
+                settings,
                 persistent_storage_clone,
+                data_dir_clone,
             ))
         }),
     )?;
@@ -59,3 +196,89 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     Ok(())
 }
+
+#[cfg(not(feature = "gui"))]
+fn run_gui(_arguments: cli::Arguments) -> Result<(), Box<dyn error::Error>> {
+    Err(
+        "built without the 'gui' feature, only '--headless' and '--disassemble' are available"
+            .into(),
+    )
+}
+
+fn run_headless(
+    arguments: cli::Arguments,
+    mut backend: backend::Backend,
+) -> Result<(), Box<dyn error::Error>> {
+    let font = arguments.font.as_ref().map(fs::read).transpose()?;
+    let program = fs::read(arguments.program.as_ref().unwrap())?;
+
+    if font
+        .as_ref()
+        .is_some_and(|font| font.len() < backend::MIN_FONT_SIZE)
+    {
+        return Err("attempt to load invalid font".into());
+    }
+
+    if let Some(seed) = arguments.seed {
+        backend.set_seed(seed);
+    }
+
+    let movie = match arguments.play {
+        Some(ref path) => {
+            let movie = movie::Movie::parse(&fs::read(path)?)?;
+            let variant = backend::VARIANTS
+                .iter()
+                .find(|variant| variant.id == movie.variant)
+                .ok_or_else(|| movie::MovieError::UnknownVariant(movie.variant.clone()))?;
+
+            backend = (variant.new)();
+            movie.prepare(backend.as_mut(), &program)?;
+
+            Some(movie)
+        }
+        None => None,
+    };
+
+    backend.load(font.as_deref(), &program)?;
+
+    let mut headless = ruschip::headless::Headless::new(backend);
+    match movie {
+        Some(ref movie) => headless.play(movie)?,
+        None => headless.run(arguments.cycles.unwrap(), arguments.instructions_per_tick())?,
+    }
+
+    print!("{}", headless.render()?);
+
+    if let Some(ref path) = arguments.screenshot {
+        let (palette, scale) = screenshot_settings(&arguments);
+        fs::write(path, headless.screenshot(scale as usize, &palette)?)?;
+    }
+
+    if let Some(movie) = movie {
+        movie.verify(headless.backend.as_mut())?;
+    }
+
+    Ok(())
+}
+
+/// Colors and scale of headless screenshots, from the config file
+#[cfg(feature = "gui")]
+fn screenshot_settings(arguments: &cli::Arguments) -> ([[u8; 3]; 4], u32) {
+    let config = load_config(&data_dir().join(config::FILE_NAME));
+
+    (
+        config.colors.palette(),
+        arguments
+            .screenshot_scale
+            .unwrap_or(config.screenshot_scale),
+    )
+}
+
+/// Colors and scale of headless screenshots, there's no config file without the GUI
+#[cfg(not(feature = "gui"))]
+fn screenshot_settings(arguments: &cli::Arguments) -> ([[u8; 3]; 4], u32) {
+    (
+        ruschip::headless::PALETTE,
+        arguments.screenshot_scale.unwrap_or(1),
+    )
+}
@@ -0,0 +1,346 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::error;
+use std::fmt;
+
+use crate::backend::{self, state, Machine};
+use crate::sha1;
+
+const MAGIC: &[u8; 4] = b"RSMV";
+/// Quirks in the order they're stored in
+const QUIRKS: [&str; 8] = [
+    "clip-sprites",
+    "copy-and-shift",
+    "display-wait",
+    "half-pixel-scrolling",
+    "increment-address",
+    "machine-code",
+    "quirky-jump",
+    "reset-flag",
+];
+const VERSION: u16 = 3;
+
+/// The keypad input of every tick of a run, along with everything else the run depends on
+pub struct Movie {
+    /// Keys held during every tick, see [`backend::interfaces::KeypadState::held`]
+    pub frames: Vec<u16>,
+    pub instructions_per_tick: u16,
+    /// RPL user flags at the start of the run
+    pub persistent_storage: Vec<u8>,
+    /// SHA-1 of the program, see [`sha1::hex_digest`]
+    pub program_hash: String,
+    pub quirks: backend::Quirks,
+    /// [`hash`] of the machine after the last frame, playback is checked against it
+    pub result_hash: Option<String>,
+    pub seed: u64,
+    /// Identifier of the variant
+    pub variant: String,
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    Desync,
+    Invalid,
+    ProgramMismatch,
+    UnknownVariant(String),
+}
+
+/// A movie that's attached to the running program
+pub enum Session {
+    Playback {
+        frame: usize,
+        movie: Movie,
+        /// RPL user flags of the playback, which are kept apart from the real ones
+        persistent_storage: Vec<u8>,
+    },
+    Recording(Movie),
+}
+
+impl Movie {
+    /// Start recording a program that was just loaded into `backend`
+    pub fn new(
+        backend: &mut dyn Machine,
+        variant: &str,
+        program: &[u8],
+        instructions_per_tick: u16,
+        persistent_storage: &[u8],
+    ) -> Self {
+        Self {
+            frames: Vec::new(),
+            instructions_per_tick,
+            persistent_storage: persistent_storage.to_vec(),
+            program_hash: sha1::hex_digest(program),
+            quirks: backend::Quirks::capture(backend),
+            result_hash: None,
+            seed: backend.seed(),
+            variant: variant.to_string(),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, MovieError> {
+        Self::read(&mut state::Reader::new(data)).map_err(|_| MovieError::Invalid)
+    }
+
+    /// Set `backend` up the way the movie was recorded, the program has to be loaded afterwards
+    ///
+    /// Fails if the program isn't the one the movie was recorded with
+    pub fn prepare(&self, backend: &mut dyn Machine, program: &[u8]) -> Result<(), MovieError> {
+        if sha1::hex_digest(program) != self.program_hash {
+            return Err(MovieError::ProgramMismatch);
+        }
+
+        self.quirks.apply(backend);
+        backend.set_seed(self.seed);
+
+        Ok(())
+    }
+
+    fn read(reader: &mut state::Reader) -> Result<Self, backend::BackendError> {
+        if reader.read_exact(MAGIC.len())? != MAGIC || reader.read_u16()? != VERSION {
+            return Err(state::invalid());
+        }
+
+        let read_string = |reader: &mut state::Reader| {
+            String::from_utf8(reader.read_bytes()?.to_vec()).map_err(|_| state::invalid())
+        };
+
+        let variant = read_string(reader)?;
+        let program_hash = read_string(reader)?;
+        let seed = reader.read_u64()?;
+        let instructions_per_tick = reader.read_u16()?;
+
+        let mut quirks = backend::Quirks::default();
+        for name in QUIRKS {
+            *quirks.get_mut(name).unwrap() = match reader.read_u8()? {
+                0 => Some(false),
+                1 => Some(true),
+                2 => None,
+                _ => return Err(state::invalid()),
+            };
+        }
+
+        let persistent_storage = reader.read_bytes()?.to_vec();
+        if persistent_storage.len() != backend::PERSISTENT_STORAGE_SIZE {
+            return Err(state::invalid());
+        }
+
+        let result_hash = match reader.read_bool()? {
+            true => Some(read_string(reader)?),
+            false => None,
+        };
+
+        let frame_count = reader.read_u32()? as usize;
+        let mut frames = Vec::with_capacity(frame_count.min(reader.remaining() / 2));
+        for _ in 0..frame_count {
+            frames.push(reader.read_u16()?);
+        }
+
+        if !reader.is_empty() {
+            return Err(state::invalid());
+        }
+
+        Ok(Self {
+            frames,
+            instructions_per_tick,
+            persistent_storage,
+            program_hash,
+            quirks,
+            result_hash,
+            seed,
+            variant,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = state::Writer::new();
+
+        writer.write_exact(MAGIC);
+        writer.write_u16(VERSION);
+        writer.write_bytes(self.variant.as_bytes());
+        writer.write_bytes(self.program_hash.as_bytes());
+        writer.write_u64(self.seed);
+        writer.write_u16(self.instructions_per_tick);
+
+        for quirk in [
+            self.quirks.clip_sprites,
+            self.quirks.copy_and_shift,
+            self.quirks.display_wait,
+            self.quirks.half_pixel_scrolling,
+            self.quirks.increment_address,
+            self.quirks.machine_code,
+            self.quirks.quirky_jump,
+            self.quirks.reset_flag,
+        ] {
+            writer.write_u8(quirk.map_or(2, |quirk| quirk as u8));
+        }
+
+        writer.write_bytes(&self.persistent_storage);
+        writer.write_bool(self.result_hash.is_some());
+        if let Some(ref result_hash) = self.result_hash {
+            writer.write_bytes(result_hash.as_bytes());
+        }
+
+        writer.write_u32(self.frames.len() as u32);
+        self.frames
+            .iter()
+            .for_each(|&frame| writer.write_u16(frame));
+
+        writer.into_inner()
+    }
+
+    /// Check the machine against the end of the recording
+    pub fn verify(&self, backend: &mut dyn Machine) -> Result<(), MovieError> {
+        match self.result_hash {
+            Some(ref result_hash) if hash(backend).ok().as_ref() != Some(result_hash) => {
+                Err(MovieError::Desync)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Desync => write!(f, "the movie ended with a different display or memory"),
+            Self::Invalid => write!(f, "attempt to play invalid movie"),
+            Self::ProgramMismatch => write!(f, "the movie was recorded with a different program"),
+            Self::UnknownVariant(id) => write!(f, "the movie's variant '{}' is unknown", id),
+        }
+    }
+}
+
+impl error::Error for MovieError {}
+
+impl Session {
+    #[inline]
+    pub fn movie(&self) -> &Movie {
+        match self {
+            Self::Playback { movie, .. } | Self::Recording(movie) => movie,
+        }
+    }
+}
+
+/// SHA-1 of the display and the memory, marks the display as clean
+pub fn hash(backend: &mut dyn Machine) -> Result<String, backend::BackendError> {
+    let mut data: Vec<u8> = backend.display_buffer()?.collect();
+    data.extend_from_slice(backend.cpu_state().memory);
+
+    Ok(sha1::hex_digest(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::interfaces;
+
+    /// Moves a sprite right while key 0 is held and down by a random amount every tick
+    const PROGRAM: [u8; 12] = [
+        0xE0, 0xA1, 0x71, 0x01, 0xC2, 0x1F, 0xA2, 0x00, 0xD1, 0x25, 0x12, 0x00,
+    ];
+
+    /// Run the frames of the movie on `backend`, returning the hash of every tick
+    fn run(backend: &mut dyn Machine, movie: &Movie) -> Vec<String> {
+        let mut keypad_state = interfaces::KeypadState::new();
+        let mut persistent_storage = movie.persistent_storage.clone();
+
+        movie
+            .frames
+            .iter()
+            .map(|&keys| {
+                keypad_state.advance();
+                keypad_state.set_held(keys);
+                backend
+                    .tick(
+                        movie.instructions_per_tick,
+                        &mut keypad_state,
+                        &mut persistent_storage,
+                    )
+                    .unwrap();
+
+                hash(backend).unwrap()
+            })
+            .collect()
+    }
+
+    fn record() -> (Movie, Vec<String>) {
+        let mut backend = (backend::VARIANTS[0].new)();
+        backend.set_seed(42);
+        backend.load(None, &PROGRAM).unwrap();
+
+        let mut movie = Movie::new(
+            backend.as_mut(),
+            backend::VARIANTS[0].id,
+            &PROGRAM,
+            10,
+            &[0; backend::PERSISTENT_STORAGE_SIZE],
+        );
+        movie.frames = (0..30).map(|i| (i % 3 == 0) as u16).collect();
+
+        let hashes = run(backend.as_mut(), &movie);
+        movie.result_hash = hashes.last().cloned();
+
+        (movie, hashes)
+    }
+
+    #[test]
+    fn replay() {
+        let (movie, hashes) = record();
+        let movie = Movie::parse(&movie.to_bytes()).unwrap();
+
+        assert_eq!(movie.variant, backend::VARIANTS[0].id);
+        assert_eq!(movie.seed, 42);
+        assert_eq!(movie.frames.len(), 30);
+
+        let mut backend = (backend::VARIANTS[0].new)();
+        assert!(matches!(
+            movie.prepare(backend.as_mut(), &PROGRAM[..10]),
+            Err(MovieError::ProgramMismatch)
+        ));
+        movie.prepare(backend.as_mut(), &PROGRAM).unwrap();
+        backend.load(None, &PROGRAM).unwrap();
+
+        assert_eq!(run(backend.as_mut(), &movie), hashes);
+        assert!(movie.verify(backend.as_mut()).is_ok());
+
+        // another seed draws the sprite elsewhere
+        backend.reset();
+        backend.set_seed(43);
+        run(backend.as_mut(), &movie);
+        assert!(matches!(
+            movie.verify(backend.as_mut()),
+            Err(MovieError::Desync)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let data = record().0.to_bytes();
+
+        let mut magic = data.clone();
+        magic[0] = b'X';
+
+        let mut version = data.clone();
+        version[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+
+        for data in [magic, version, trailing, data[..data.len() - 1].to_vec()] {
+            assert!(matches!(Movie::parse(&data), Err(MovieError::Invalid)));
+        }
+    }
+}
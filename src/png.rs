@@ -0,0 +1,312 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::bits::BitWriter;
+
+const DISTANCE_BASES: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const LENGTH_BASES: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const MAX_DISTANCE: usize = 32768;
+const MAX_LENGTH: usize = 258;
+const MIN_LENGTH: usize = 3;
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    b << 16 | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            0 => crc >> 1,
+            _ => crc >> 1 ^ 0xEDB88320,
+        })
+    })
+}
+
+/// Compress `data` into a single block of fixed Huffman codes
+///
+/// Only repeats of the previous byte and of the previous row are looked for, which is where almost
+/// all of the redundancy of a scaled up display is
+fn deflate(data: &[u8], row_length: usize) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // the final block, compressed with the fixed codes
+    writer.write_bits(0b011, 3);
+
+    let mut i = 0;
+    while i < data.len() {
+        let (distance, length) = [1, row_length]
+            .into_iter()
+            .filter(|&distance| distance <= i && distance <= MAX_DISTANCE)
+            .map(|distance| {
+                let length = data[i..]
+                    .iter()
+                    .zip(&data[i - distance..])
+                    .take(MAX_LENGTH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (distance, length)
+            })
+            .max_by_key(|&(_, length)| length)
+            .unwrap_or((0, 0));
+
+        if length < MIN_LENGTH {
+            write_symbol(&mut writer, data[i] as u32);
+            i += 1;
+            continue;
+        }
+
+        let code = LENGTH_BASES.partition_point(|&base| base <= length) - 1;
+        write_symbol(&mut writer, 257 + code as u32);
+        writer.write_bits(
+            (length - LENGTH_BASES[code]) as u32,
+            LENGTH_EXTRA_BITS[code] as u32,
+        );
+
+        let code = DISTANCE_BASES.partition_point(|&base| base <= distance) - 1;
+        write_code(&mut writer, code as u32, 5);
+        writer.write_bits(
+            (distance - DISTANCE_BASES[code]) as u32,
+            DISTANCE_EXTRA_BITS[code] as u32,
+        );
+
+        i += length;
+    }
+
+    write_symbol(&mut writer, 256);
+    writer.finish()
+}
+
+/// Encode an image of palette indices as an indexed-color PNG
+///
+/// `pixels` are the indices row by row, `palette` has an RGB color for every index that's used
+pub fn encode(width: usize, height: usize, pixels: &[u8], palette: &[[u8; 3]]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per pixel, indexed color, deflate, adaptive filtering and no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    // every row starts with the type of its filter, which is always none
+    let mut data = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks_exact(width) {
+        data.push(0);
+        data.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"PLTE", palette.concat().as_slice());
+    write_chunk(&mut png, b"IDAT", &zlib(&data, width + 1));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+/// Scale an image of palette indices up, every pixel becomes `scale` by `scale` pixels
+pub fn scale(width: usize, pixels: &[u8], scale: usize) -> Vec<u8> {
+    pixels
+        .chunks_exact(width)
+        .flat_map(|row| {
+            let row: Vec<u8> = row
+                .iter()
+                .flat_map(|&pixel| [pixel].repeat(scale))
+                .collect();
+            row.repeat(scale)
+        })
+        .collect()
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Write a Huffman code, which are packed most significant bit first
+fn write_code(writer: &mut BitWriter, code: u32, length: u32) {
+    writer.write_bits(code.reverse_bits() >> (32 - length), length);
+}
+
+/// Write a literal or length symbol with the fixed Huffman codes
+fn write_symbol(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => write_code(writer, 0x30 + symbol, 8),
+        144..=255 => write_code(writer, 0x190 + symbol - 144, 9),
+        256..=279 => write_code(writer, symbol - 256, 7),
+        _ => write_code(writer, 0xC0 + symbol - 280, 8),
+    }
+}
+
+/// Wrap the deflated `data` in a zlib stream
+fn zlib(data: &[u8], row_length: usize) -> Vec<u8> {
+    // deflate with a 32 KiB window and the fastest compression level
+    let mut stream = vec![0x78, 0x01];
+    stream.extend_from_slice(&deflate(data, row_length));
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompress a zlib stream of a single block of fixed Huffman codes, which is what [`zlib`]
+    /// writes
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        let mut bits = stream[2..stream.len() - 4]
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i & 1) as usize));
+        let mut read = |count: usize, reversed: bool| {
+            (0..count).fold(0, |value, i| match reversed {
+                true => value << 1 | bits.next().unwrap(),
+                false => value | bits.next().unwrap() << i,
+            })
+        };
+
+        assert_eq!(read(3, false), 0b011, "a final block of fixed codes");
+
+        let mut data: Vec<u8> = Vec::new();
+        loop {
+            let mut code = read(7, true);
+            let symbol = match code {
+                0..=0x17 => 256 + code,
+                _ => {
+                    code = code << 1 | read(1, true);
+                    match code {
+                        0x30..=0xBF => code - 0x30,
+                        0xC0..=0xC7 => 280 + code - 0xC0,
+                        _ => 144 + (code << 1 | read(1, true)) - 0x190,
+                    }
+                }
+            };
+
+            match symbol {
+                0..=255 => data.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let code = symbol - 257;
+                    let length = LENGTH_BASES[code] + read(LENGTH_EXTRA_BITS[code] as usize, false);
+                    let code = read(5, true);
+                    let distance =
+                        DISTANCE_BASES[code] + read(DISTANCE_EXTRA_BITS[code] as usize, false);
+
+                    for _ in 0..length {
+                        data.push(data[data.len() - distance]);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn chunks() {
+        let pixels = [0, 1, 1, 0, 1, 0, 0, 1];
+        let png = encode(4, 2, &pixels, &[[0, 0, 0], [0xFF, 0xFF, 0xFF]]);
+
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(
+            png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (chunk, crc) = rest[4..].split_at(4 + length);
+            assert_eq!(crc32(chunk).to_be_bytes(), crc[..4]);
+
+            chunks.push((&chunk[..4], &chunk[4..]));
+            rest = &crc[4..];
+        }
+
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 4, 0, 0, 0, 2, 8, 3, 0, 0, 0]);
+        assert_eq!(chunks[1].1, [0, 0, 0, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(inflate(chunks[2].1), [0, 0, 1, 1, 0, 0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn scaled() {
+        assert_eq!(scale(2, &[0, 1, 2, 3], 1), [0, 1, 2, 3]);
+        assert_eq!(
+            scale(2, &[0, 1, 2, 3], 2),
+            [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 3, 3, 2, 2, 3, 3]
+        );
+    }
+
+    #[test]
+    fn inflate_round_trip() {
+        // a scaled up display repeats pixels and rows, long runs need more than one match
+        let row_length = 129;
+        let mut display = Vec::new();
+        for y in 0..64 {
+            display.push(0);
+            display.extend((0..128).map(|x| ((x / 4 + y / 4) % 2) as u8));
+        }
+        display.extend(vec![1; 1000]);
+
+        // noise, which has hardly any matches
+        let mut seed = 1u32;
+        let noise: Vec<u8> = (0..4000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+
+        for data in [&[][..], &[7], &display, &noise] {
+            assert_eq!(inflate(&zlib(data, row_length)), data);
+        }
+    }
+}
@@ -14,7 +14,11 @@
 //    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::cell;
+use std::collections::BTreeSet;
 use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::mem;
 use std::path;
 use std::rc;
 use std::time;
@@ -22,35 +26,77 @@ use std::time;
 use eframe::egui;
 use egui::color_picker;
 
-use crate::backend;
+use crate::backend::{self, disassembler};
+use crate::config;
+use crate::database;
 use crate::frontend;
+use crate::movie;
+use crate::sha1;
 
 mod file_picker;
 
+const DISASSEMBLY_CONTEXT: usize = 8; // instructions listed on either side of the program counter
 const ERROR_DISPLAY_DURATION: time::Duration = time::Duration::from_secs(2);
 const MENU_SPACING: f32 = 2.5;
 pub(crate) const PRIMARY_COLOR: egui::Color32 = egui::Color32::from_rgb(0x81, 0x5B, 0xA4); // #815BA4
+const PROFILES_DIRECTORY: &str = "profiles";
+const REPAINT_INTERVAL: time::Duration = time::Duration::from_millis(1000 / 60);
+const SCREENSHOTS_DIRECTORY: &str = "screenshots";
 pub(crate) const SECONDARY_COLOR: egui::Color32 = egui::Color32::from_rgb(0x1C, 0x1C, 0x1C); // #1C1C1C
-const TICK_INTERVAL: time::Duration = time::Duration::from_millis(1000 / 60);
+const STATE_SLOT_KEYS: [egui::Key; 9] = [
+    egui::Key::F1,
+    egui::Key::F2,
+    egui::Key::F3,
+    egui::Key::F4,
+    egui::Key::F5,
+    egui::Key::F6,
+    egui::Key::F7,
+    egui::Key::F8,
+    egui::Key::F9,
+];
+const STATES_DIRECTORY: &str = "states";
+pub const WINDOW_TITLE: &str = "Ruschip";
 
 pub struct App {
-    _stream: rodio::OutputStream,
+    data_dir: path::PathBuf,
     display_texture: egui::TextureId,
     file_picker: file_picker::FilePicker,
     frontend: frontend::Frontend,
-    persistent_storage: rc::Rc<cell::RefCell<[u8; 8]>>,
+    persistent_storage: rc::Rc<cell::RefCell<[u8; backend::PERSISTENT_STORAGE_SIZE]>>,
     state: State,
+    variants: Vec<backend::Variant>,
 }
 
-#[derive(PartialEq, Eq)]
-enum BackendSelection {
-    Chip8,
-    SuperChip,
+/// What the app starts up with
+pub struct Settings {
+    pub backend: backend::Backend,
+    /// The quirks in it are expected to be applied to the backend already
+    pub config: config::Config,
+    /// Movie to play back the first time the program is started
+    pub play_movie: Option<path::PathBuf>,
+    /// Where to save a movie of every run of the program
+    pub record_movie: Option<path::PathBuf>,
+    /// Start running the configured program right away
+    pub start: bool,
+    /// Index of the backend's variant in the list of variants passed to [`App::new`]
+    pub variant: usize,
 }
 
 enum ColorSelection {
     Active,
     Inactive,
+    Overlap,
+    Secondary,
+}
+
+enum DebuggerAction {
+    Continue,
+    Pause,
+    RunTo(usize),
+    StepInto,
+    StepOut,
+    StepOver,
+    ToggleBreakpoint(usize),
 }
 
 struct Error {
@@ -77,25 +123,55 @@ enum PathSelection {
     Program,
 }
 
+enum StateAction {
+    Load(usize),
+    Save(usize),
+}
+
 enum QuirkSelection {
     CopyAndShift,
+    DisplayWait,
     IncrementAddress,
+    MachineCode,
     QuirkyJump,
     ResetFlag,
 }
 
 struct State {
+    /// CHIP-8 key that the next key press is bound to
+    binding: Option<usize>,
+    debugger: bool,
+    /// Settings from before the database entry or the profile of the running program was applied,
+    /// they're restored when another program is started and saved to the config file instead
+    defaults: Option<config::Config>,
     emulation: Emulation,
     error: Error,
     menu: Menu,
     font_path: Option<path::PathBuf>,
     program_path: Option<path::PathBuf>,
     path_selection: PathSelection,
+    play_movie: Option<path::PathBuf>,
+    /// SHA-1 of the program that was started last, its profile isn't applied again on restarts
+    program_hash: Option<String>,
+    raw_clips: bool,
+    record_movie: Option<path::PathBuf>,
+    rewinding: bool,
+    screenshot_directory: Option<path::PathBuf>,
+    screenshot_scale: u32,
+    /// Seed the program is started with, a new one is picked every start if not given
+    seed: Option<u64>,
+    variant: usize,
+    /// Title to give the window on the next update
+    window_title: Option<String>,
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        if self.state.emulation != Emulation::Stopped {
+        if let Some(title) = self.state.window_title.take() {
+            frame.set_window_title(&title);
+        }
+
+        if self.state.emulation != Emulation::Stopped && self.state.binding.is_none() {
             self.handle_input(ctx);
         }
 
@@ -106,25 +182,27 @@ impl eframe::App for App {
         }
 
         if self.state.emulation == Emulation::Running {
-            ctx.request_repaint_after(TICK_INTERVAL);
+            ctx.request_repaint_after(REPAINT_INTERVAL);
 
-            let mut persistent_storage = self.persistent_storage.borrow_mut();
-            if let Err(error) = self.frontend.tick(ctx, persistent_storage.as_mut()) {
-                if error.is_fatal() {
-                    self.state.error.timestamp = time::Instant::now();
-                    self.state.error.message.clear();
-                    let _ = write!(self.state.error.message, "fatal error, {}", error);
+            let result = match self.state.rewinding {
+                true => self.frontend.rewind().map(|_| false),
+                false => self
+                    .frontend
+                    .tick(ctx, self.persistent_storage.borrow_mut().as_mut()),
+            };
 
-                    self.state.emulation = Emulation::Stopped;
-                    self.state.menu = Menu::Configuration;
-                    ctx.request_repaint();
-                    return;
+            match result {
+                Ok(true) => self.state.emulation = Emulation::Suspended,
+                Ok(false) => (),
+                Err(error) => {
+                    if self.report_error(ctx, error) {
+                        return;
+                    }
                 }
-
-                eprintln!("{}", error);
             }
 
             if self.frontend.backend.has_program_exited() {
+                self.finish_movie();
                 self.state.emulation = Emulation::Stopped;
                 self.state.menu = Menu::Configuration;
                 ctx.request_repaint();
@@ -154,12 +232,58 @@ impl eframe::App for App {
             .show(ctx, |ui| {
                 ui.add(egui::Image::new(self.display_texture, size));
             });
+
+        if self.frontend.is_capturing() {
+            egui::Area::new("Capture Indicator")
+                .fixed_pos(egui::pos2(MENU_SPACING * 4.0, MENU_SPACING * 4.0))
+                .show(ctx, |ui| ui.colored_label(egui::Color32::RED, "● REC"));
+        }
+
+        if self.state.debugger {
+            self.debugger_window(ctx);
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.finish_capture();
+        self.finish_movie();
+        self.save_config();
     }
 }
 
 impl App {
+    /// Switch to the variant of a profile and apply its quirks and frontend settings
+    fn apply_profile(&mut self, profile: &config::Config) {
+        if let Some(variant) = profile
+            .variant
+            .as_ref()
+            .and_then(|id| self.variants.iter().position(|variant| variant.id == id))
+        {
+            if variant != self.state.variant {
+                self.frontend.backend = (self.variants[variant].new)();
+                self.state.variant = variant;
+            }
+        }
+
+        profile.quirks.apply(self.frontend.backend.as_mut());
+        self.configure(profile);
+    }
+
     fn handle_input(&mut self, ctx: &egui::Context) {
+        let mut advance_frame = false;
+        let mut capture = false;
+        let mut debugger_action = None;
+        let mut screenshot = false;
+        let mut state_action = None;
+
         ctx.input_mut(|input| {
+            // a movie would no longer match the input that led to the state
+            self.state.rewinding = self.state.menu == Menu::Inactive
+                && self.frontend.movie().is_none()
+                && input.key_down(egui::Key::Backspace);
+            self.frontend.fast_forward =
+                self.state.menu == Menu::Inactive && input.key_down(egui::Key::Tab);
+
             if input.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
                 if self.state.menu == Menu::Inactive {
                     self.frontend.suspend();
@@ -185,24 +309,98 @@ impl App {
                 self.state.emulation = Emulation::Running;
                 return;
             }
+
+            if self.state.menu == Menu::Inactive
+                && input.consume_key(egui::Modifiers::NONE, egui::Key::F12)
+            {
+                self.state.debugger = !self.state.debugger;
+                return;
+            }
+
+            if self.state.menu == Menu::Inactive
+                && input.consume_key(egui::Modifiers::CTRL, egui::Key::P)
+            {
+                screenshot = true;
+                return;
+            }
+
+            if self.state.menu == Menu::Inactive
+                && input.consume_key(egui::Modifiers::CTRL, egui::Key::G)
+            {
+                capture = true;
+                return;
+            }
+
+            if self.state.menu == Menu::Inactive
+                && self.state.emulation == Emulation::Suspended
+                && input.consume_key(egui::Modifiers::NONE, egui::Key::Enter)
+            {
+                advance_frame = true;
+                return;
+            }
+
+            if self.state.debugger
+                && self.state.menu == Menu::Inactive
+                && self.state.emulation == Emulation::Suspended
+            {
+                if input.consume_key(egui::Modifiers::NONE, egui::Key::F10) {
+                    debugger_action = Some(DebuggerAction::StepOver);
+                } else if input.consume_key(egui::Modifiers::SHIFT, egui::Key::F11) {
+                    debugger_action = Some(DebuggerAction::StepOut);
+                } else if input.consume_key(egui::Modifiers::NONE, egui::Key::F11) {
+                    debugger_action = Some(DebuggerAction::StepInto);
+                }
+            }
+
+            if self.state.menu == Menu::Inactive {
+                for (i, key) in STATE_SLOT_KEYS.into_iter().enumerate() {
+                    if input.consume_key(egui::Modifiers::SHIFT, key) {
+                        state_action = Some(StateAction::Save(i + 1));
+                    } else if input.consume_key(egui::Modifiers::NONE, key) {
+                        state_action = Some(StateAction::Load(i + 1));
+                    }
+                }
+            }
         });
+
+        match state_action {
+            Some(StateAction::Load(slot)) => self.load_state(slot),
+            Some(StateAction::Save(slot)) => self.save_state(slot),
+            None => (),
+        }
+
+        if let Some(action) = debugger_action {
+            self.debug(ctx, action);
+        }
+
+        if screenshot {
+            self.save_screenshot();
+        }
+
+        if capture {
+            match self.frontend.is_capturing() {
+                true => self.finish_capture(),
+                false => {
+                    if let Err(error) = self.frontend.start_capture() {
+                        eprintln!("couldn't start capturing, {}", error);
+                    }
+                }
+            }
+        }
+
+        if advance_frame {
+            let result = self
+                .frontend
+                .advance_frame(ctx, self.persistent_storage.borrow_mut().as_mut());
+
+            if let Err(error) = result {
+                self.report_error(ctx, error);
+            }
+        }
     }
 
     fn backend_selection_menu(&mut self, ctx: &egui::Context) {
-        const BACKENDS: [(&str, &str, BackendSelection); 2] = [
-            (
-                "CHIP-8",
-                "The original CHIP-8 interpreter",
-                BackendSelection::Chip8,
-            ),
-            (
-                "SUPER-CHIP",
-                "A backwards-compatible extended version of CHIP-8 with higher resolution mode and new instructions",
-                BackendSelection::SuperChip,
-            ),
-        ];
-
-        let current_selection = BackendSelection::get(&self.frontend.backend);
+        let mut changed = false;
 
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical()
@@ -210,24 +408,31 @@ impl App {
                 .show(ui, |ui| {
                     ui.heading("Backends");
                     ui.separator();
-                    for item_data in BACKENDS {
+                    for (i, variant) in self.variants.iter().enumerate() {
                         ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
                             if ui
                                 .selectable_label(
                                     false,
-                                    egui::RichText::new(item_data.0)
+                                    egui::RichText::new(variant.name)
                                         .color(PRIMARY_COLOR)
                                         .heading(),
                                 )
                                 .clicked()
                             {
-                                if current_selection != item_data.2 {
-                                    self.frontend.backend = item_data.2.into_backend();
+                                if self.state.variant != i {
+                                    self.frontend.backend = (variant.new)();
+                                    self.state.variant = i;
+                                }
+                                if let Some(ref mut defaults) = self.state.defaults {
+                                    defaults.quirks =
+                                        backend::Quirks::capture(self.frontend.backend.as_mut());
+                                    defaults.variant = Some(variant.id.to_string());
                                 }
+                                changed = true;
                                 self.state.menu = Menu::Configuration;
                             }
                             ui.label({
-                                egui::RichText::new(item_data.1)
+                                egui::RichText::new(variant.description)
                                     .color(egui::Color32::GRAY)
                                     .small()
                             });
@@ -235,6 +440,29 @@ impl App {
                     }
                 })
         });
+
+        if changed {
+            self.save_config();
+        }
+    }
+
+    /// The current settings, as they'd be written to the config file
+    fn config(&mut self) -> config::Config {
+        config::Config {
+            colors: self.frontend.colors,
+            font_path: self.state.font_path.clone(),
+            instructions_per_tick: self.frontend.instructions_per_tick,
+            key_map: self.frontend.key_map.clone(),
+            program_path: self.state.program_path.clone(),
+            quirks: backend::Quirks::capture(self.frontend.backend.as_mut()),
+            raw_clips: self.state.raw_clips,
+            rewind_depth: self.frontend.rewind_depth,
+            screenshot_directory: self.state.screenshot_directory.clone(),
+            screenshot_scale: self.state.screenshot_scale,
+            seed: self.state.seed,
+            speed: self.frontend.speed,
+            variant: Some(self.variants[self.state.variant].id.to_string()),
+        }
     }
 
     fn configuration_menu(&mut self, ctx: &egui::Context) {
@@ -243,23 +471,69 @@ impl App {
             ("Inactive Color", ColorSelection::Inactive),
         ];
 
+        const XOCHIP_COLOR_PICKERS: [(&str, ColorSelection); 2] = [
+            ("Secondary Color", ColorSelection::Secondary),
+            ("Overlap Color", ColorSelection::Overlap),
+        ];
+
+        // the layout of the COSMAC VIP's keypad
+        const KEYPAD_LAYOUT: [usize; backend::KEY_COUNT] = [
+            0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+        ];
+
         const PATH_SELECTORS: [(&str, PathSelection); 2] = [
             ("Font", PathSelection::Font),
             ("Program", PathSelection::Program),
         ];
 
-        const QUIRK_TOGGLES: [(&str, &str, QuirkSelection); 4] = [
+        const QUIRK_TOGGLES: [(&str, &str, QuirkSelection); 6] = [
             ("Copy and Shift", "Copy the content of second operand register to the first operand register before shifting", QuirkSelection::CopyAndShift),
+            ("Display Wait", "Wait for the next frame after drawing a sprite, like the COSMAC VIP waits for the display interrupt", QuirkSelection::DisplayWait),
             ("Increment Address", " Increment the address register after executing SAVE and LOAD instructions", QuirkSelection::IncrementAddress),
+            ("Machine Code", "Run 0NNN as a call to a machine code subroutine of the 1802, for the hybrid programs of the COSMAC VIP", QuirkSelection::MachineCode),
             ("Quirky Jump", "The 'jump to some address plus v0' instruction (Bnnn) doesn't use v0, but vX instead where X is the highest nibble of nnn", QuirkSelection::QuirkyJump),
             ("Reset Flag", "Reset the flag register after executing AND, OR and XOR instructions", QuirkSelection::ResetFlag),
         ];
 
+        // settings are written to the config file as soon as they change
+        let mut changed = false;
+
+        if let Some(binding) = self.state.binding {
+            ctx.input_mut(|input| {
+                let Some(i) = input.events.iter().position(|event| {
+                    matches!(
+                        event,
+                        egui::Event::Key {
+                            pressed: true,
+                            repeat: false,
+                            ..
+                        }
+                    )
+                }) else {
+                    return;
+                };
+
+                // taken out so that the focused button doesn't see it
+                let egui::Event::Key { key, .. } = input.events.remove(i) else {
+                    unreachable!();
+                };
+
+                let keys = &mut self.frontend.key_map[binding];
+                if key != egui::Key::Escape && !keys.contains(&key) {
+                    keys.push(key);
+                    changed = true;
+                }
+
+                self.state.binding = None;
+            });
+        }
+
         if let Some(path) = self.file_picker.show(ctx) {
             match self.state.path_selection {
                 PathSelection::Font => self.state.font_path.insert(path.to_path_buf()),
                 PathSelection::Program => self.state.program_path.insert(path.to_path_buf()),
             };
+            changed = true;
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -303,6 +577,7 @@ impl App {
                                             .clicked()
                                     {
                                         *selected_path = None;
+                                        changed = true;
                                     }
 
                                     let file_name = selected_path
@@ -331,12 +606,14 @@ impl App {
 
                             for item_data in QUIRK_TOGGLES {
                                 menu_item(ui, item_data.0, |ui| {
-                                    ui.checkbox(
-                                        item_data
-                                            .2
-                                            .get_quirk_mut(self.frontend.backend.get_options_mut()),
-                                        "",
-                                    );
+                                    changed |= ui
+                                        .checkbox(
+                                            item_data
+                                                .2
+                                                .get_quirk_mut(self.frontend.backend.options_mut()),
+                                            "",
+                                        )
+                                        .changed();
                                 });
                                 ui.label({
                                     egui::RichText::new(item_data.1)
@@ -347,26 +624,23 @@ impl App {
                                 ui.add_space(MENU_SPACING);
                             }
 
-                            menu_item(ui, "Clip Sprites", |ui| {
-                                ui.checkbox(
-                                    &mut self.frontend.backend.get_display_options_mut().clip_sprites,
-                                    "",
-                                );
-                            });
-                            ui.label({
-                                egui::RichText::new("Clip the sprites drawn beyond the edge of the screen (wrap around if off)")
-                                    .color(egui::Color32::GRAY)
-                                    .small()
-                            });
+                            let supports_scrolling = self.frontend.backend.supports_scrolling();
 
-                            match self.frontend.backend {
-                                backend::Backend::Chip8(..) => (),
-                                backend::Backend::SuperChip(..) => {
+                            if let Some(display_options) = self.frontend.backend.display_options_mut() {
+                                menu_item(ui, "Clip Sprites", |ui| {
+                                    changed |= ui.checkbox(&mut display_options.clip_sprites, "").changed();
+                                });
+                                ui.label({
+                                    egui::RichText::new("Clip the sprites drawn beyond the edge of the screen (wrap around if off)")
+                                        .color(egui::Color32::GRAY)
+                                        .small()
+                                });
+
+                                if supports_scrolling {
                                     menu_item(ui, "Half Pixel Scrolling", |ui| {
-                                        ui.checkbox(
-                                            &mut self.frontend.backend.get_display_options_mut().half_pixel_scrolling,
-                                            "",
-                                        );
+                                        changed |= ui
+                                            .checkbox(&mut display_options.half_pixel_scrolling, "")
+                                            .changed();
                                     });
                                     ui.label({
                                         egui::RichText::new("Scroll same number of pixels in both resolution modes (scroll twice the pixels in low resolution if off)")
@@ -385,16 +659,161 @@ impl App {
 
                             for item_data in COLOR_PICKERS {
                                 menu_item(ui, item_data.0, |ui| {
-                                    color_picker::color_edit_button_srgba(
+                                    changed |= color_picker::color_edit_button_srgba(
                                         ui,
                                         item_data.1.get_color_mut(&mut self.frontend.colors),
                                         color_picker::Alpha::Opaque,
-                                    );
+                                    )
+                                    .changed();
                                 });
 
                                 ui.add_space(MENU_SPACING);
                             }
 
+                            if self.frontend.backend.plane_count() > 1 {
+                                for item_data in XOCHIP_COLOR_PICKERS {
+                                    menu_item(ui, item_data.0, |ui| {
+                                        changed |= color_picker::color_edit_button_srgba(
+                                            ui,
+                                            item_data.1.get_color_mut(&mut self.frontend.colors),
+                                            color_picker::Alpha::Opaque,
+                                        )
+                                        .changed();
+                                    });
+
+                                    ui.add_space(MENU_SPACING);
+                                }
+                            }
+
+                            menu_item(ui, "Instructions Per Frame", |ui| {
+                                changed |= ui.add(
+                                    egui::Slider::new(&mut self.frontend.instructions_per_tick, 1..=1000)
+                                        .logarithmic(true),
+                                ).changed();
+                            });
+                            ui.label({
+                                egui::RichText::new("Instructions executed every 60th of a second, SUPER-CHIP and XO-CHIP programs often expect far more than CHIP-8 ones")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Speed", |ui| {
+                                changed |= ui.add(
+                                    egui::Slider::new(&mut self.frontend.speed, frontend::SPEED_RANGE)
+                                        .suffix(" %"),
+                                ).changed();
+                            });
+                            ui.label({
+                                egui::RichText::new("Slow the emulation down to a fraction of real time, hold Tab to fast-forward and press Enter to advance a single frame while paused")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Rewind Depth", |ui| {
+                                changed |= ui.add(
+                                    egui::Slider::new(&mut self.frontend.rewind_depth, 0..=60)
+                                        .suffix(" s"),
+                                ).changed();
+                            });
+                            ui.label({
+                                egui::RichText::new("Seconds of gameplay that can be rewound by holding Backspace (disabled if 0)")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Screenshot Scale", |ui| {
+                                changed |= ui.add(
+                                    egui::Slider::new(&mut self.state.screenshot_scale, frontend::SCREENSHOT_SCALE_RANGE)
+                                        .suffix("×"),
+                                ).changed();
+                            });
+                            ui.label({
+                                egui::RichText::new("Press Ctrl+P to save the display as a PNG, the screenshots directory can be changed in the config file")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Raw Clips", |ui| {
+                                changed |= ui.checkbox(&mut self.state.raw_clips, "").changed();
+                            });
+                            ui.label({
+                                egui::RichText::new("Press Ctrl+G to start and stop capturing a GIF into the screenshots directory, raw clips also save every frame as PNG and the buzzer as WAV")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Random Seed", |ui| {
+                                if let Some(ref mut seed) = self.state.seed {
+                                    changed |= ui.add(egui::DragValue::new(seed)).changed();
+                                }
+
+                                let mut fixed = self.state.seed.is_some();
+                                if ui.checkbox(&mut fixed, "Fixed").changed() {
+                                    self.state.seed = fixed.then(|| self.frontend.backend.seed());
+                                    changed = true;
+                                }
+                            });
+                            ui.label({
+                                egui::RichText::new("Start the program with the same seed every time so that random numbers repeat, a new seed is picked on every start otherwise")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
+                            menu_item(ui, "Keypad", |ui| {
+                                if ui.button("⟲ Reset").clicked() {
+                                    self.frontend.key_map = frontend::default_key_map();
+                                    self.state.binding = None;
+                                    changed = true;
+                                }
+                            });
+                            ui.label({
+                                egui::RichText::new("Click a key and press the keyboard key to bind to it (Escape cancels), right click a key to clear its bindings")
+                                    .color(egui::Color32::GRAY)
+                                    .small()
+                            });
+
+                            egui::Grid::new("Keypad").num_columns(4).show(ui, |ui| {
+                                for row in KEYPAD_LAYOUT.chunks(4) {
+                                    for &key in row {
+                                        let names: Vec<&str> = self.frontend.key_map[key]
+                                            .iter()
+                                            .map(|key| key.symbol_or_name())
+                                            .collect();
+
+                                        let text = match self.state.binding == Some(key) {
+                                            true => format!("{:X}: Press a key…", key),
+                                            false if names.is_empty() => format!("{:X}: None", key),
+                                            false => format!("{:X}: {}", key, names.join(", ")),
+                                        };
+
+                                        let response = ui.button(text);
+                                        if response.clicked() {
+                                            self.state.binding = Some(key);
+                                        }
+                                        if response.secondary_clicked() {
+                                            self.frontend.key_map[key].clear();
+                                            changed = true;
+                                        }
+                                    }
+
+                                    ui.end_row();
+                                }
+                            });
+
+                            ui.add_space(MENU_SPACING);
+
                             if self.state.program_path.is_some()
                                 && self.state.emulation == Emulation::Stopped
                             {
@@ -414,6 +833,7 @@ impl App {
 
                         ui.with_layout(egui::Layout::top_down_justified(egui::Align::Min), |ui| {
                             if ui.button("⟲ Reset").clicked() {
+                                self.finish_movie();
                                 self.frontend.reset();
                                 self.state.emulation = Emulation::Running;
                                 self.state.menu = Menu::Inactive;
@@ -421,19 +841,360 @@ impl App {
 
                             ui.add_space(MENU_SPACING);
 
+                            if ui
+                                .button("💾 Save Profile")
+                                .on_hover_text("Remember the current settings for this program, they're applied whenever it's started")
+                                .clicked()
+                            {
+                                self.save_profile();
+                            }
+
+                            ui.add_space(MENU_SPACING);
+
                             if ui.button("■ Stop").clicked() {
+                                self.finish_movie();
                                 self.state.emulation = Emulation::Stopped;
                             }
                         });
                     }
             });
         });
+
+        if changed {
+            self.save_config();
+        }
+    }
+
+    /// Apply the frontend settings of a config, the variant, quirks and paths are left alone
+    fn configure(&mut self, config: &config::Config) {
+        self.frontend.colors = config.colors;
+        self.frontend.instructions_per_tick = config.instructions_per_tick;
+        self.frontend.key_map = config.key_map.clone();
+        self.frontend.rewind_depth = config.rewind_depth;
+        self.frontend.speed = config.speed;
+        self.state.seed = config.seed;
+    }
+
+    fn debug(&mut self, ctx: &egui::Context, action: DebuggerAction) {
+        let result = match action {
+            DebuggerAction::Continue => Ok(()),
+            DebuggerAction::Pause => {
+                self.frontend.suspend();
+                self.state.emulation = Emulation::Suspended;
+                return;
+            }
+            DebuggerAction::RunTo(address) => {
+                self.frontend.debugger.run_to(address);
+                Ok(())
+            }
+            DebuggerAction::StepInto => self
+                .frontend
+                .step(ctx, self.persistent_storage.borrow_mut().as_mut()),
+            DebuggerAction::StepOut => {
+                self.frontend
+                    .debugger
+                    .step_out(self.frontend.backend.as_ref());
+                Ok(())
+            }
+            DebuggerAction::StepOver => self
+                .frontend
+                .step_over(ctx, self.persistent_storage.borrow_mut().as_mut()),
+            DebuggerAction::ToggleBreakpoint(address) => {
+                self.frontend.debugger.toggle_breakpoint(address);
+                return;
+            }
+        };
+
+        if let Err(error) = result {
+            self.report_error(ctx, error);
+            return;
+        }
+
+        if matches!(action, DebuggerAction::Continue) || self.frontend.debugger.has_target() {
+            self.state.emulation = Emulation::Running;
+            ctx.request_repaint();
+        }
+    }
+
+    fn debugger_window(&mut self, ctx: &egui::Context) {
+        let mut action = None;
+        let suspended = self.state.emulation == Emulation::Suspended;
+        let cpu_state = self.frontend.backend.cpu_state();
+
+        egui::Window::new("Debugger")
+            .open(&mut self.state.debugger)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if suspended {
+                        if ui.button("▶ Continue").clicked() {
+                            action = Some(DebuggerAction::Continue);
+                        }
+                    } else if ui.button("⏸ Pause").clicked() {
+                        action = Some(DebuggerAction::Pause);
+                    }
+
+                    ui.add_enabled_ui(suspended, |ui| {
+                        if ui.button("Step Into").on_hover_text("F11").clicked() {
+                            action = Some(DebuggerAction::StepInto);
+                        }
+                        if ui.button("Step Over").on_hover_text("F10").clicked() {
+                            action = Some(DebuggerAction::StepOver);
+                        }
+                        if ui
+                            .add_enabled(!cpu_state.stack.is_empty(), egui::Button::new("Step Out"))
+                            .on_hover_text("Shift + F11")
+                            .clicked()
+                        {
+                            action = Some(DebuggerAction::StepOut);
+                        }
+                    });
+                });
+
+                ui.separator();
+
+                egui::Grid::new("Registers").show(ui, |ui| {
+                    for (i, register) in cpu_state.general.iter().enumerate() {
+                        ui.monospace(format!("V{:X} {:02X}", i, register));
+
+                        if i % 4 == 3 {
+                            ui.end_row();
+                        }
+                    }
+
+                    ui.monospace(format!("PC {:04X}", cpu_state.index));
+                    ui.monospace(format!("I  {:04X}", cpu_state.address));
+                    ui.monospace(format!("DT {:02X}", cpu_state.timers.delay()));
+                    ui.monospace(format!("ST {:02X}", cpu_state.timers.sound));
+                    ui.end_row();
+                });
+
+                ui.separator();
+
+                menu_item(ui, "Stack", |ui| {
+                    let mut stack = String::new();
+                    for address in cpu_state.stack.iter().rev() {
+                        let _ = write!(stack, "{:04X} ", address);
+                    }
+
+                    ui.monospace(if stack.is_empty() {
+                        "Empty"
+                    } else {
+                        stack.trim_end()
+                    });
+                });
+
+                ui.separator();
+
+                let start = cpu_state
+                    .index
+                    .saturating_sub(DISASSEMBLY_CONTEXT * mem::size_of::<backend::Instruction>());
+                let end = (cpu_state.index
+                    + (DISASSEMBLY_CONTEXT + 1) * mem::size_of::<backend::Instruction>())
+                .min(cpu_state.memory.len() - 1);
+
+                let instruction_set = self.frontend.backend.instruction_set();
+                let labels = BTreeSet::new();
+
+                let mut address = start;
+                while address < end {
+                    let (instruction, length) = disassembler::decode(
+                        &cpu_state.memory[address..],
+                        instruction_set,
+                        disassembler::Syntax::Cowgod,
+                        &labels,
+                    );
+                    let breakpoint = self.frontend.debugger.breakpoints.contains(&address);
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add(
+                                egui::Label::new(
+                                    egui::RichText::new(if breakpoint { "●" } else { "○" }).color(
+                                        match breakpoint {
+                                            true => egui::Color32::RED,
+                                            false => egui::Color32::GRAY,
+                                        },
+                                    ),
+                                )
+                                .sense(egui::Sense::click()),
+                            )
+                            .on_hover_text("Toggle Breakpoint")
+                            .clicked()
+                        {
+                            action = Some(DebuggerAction::ToggleBreakpoint(address));
+                        }
+
+                        let text = egui::RichText::new(format!(
+                            "{} {:04X}  {}",
+                            if address == cpu_state.index {
+                                "▶"
+                            } else {
+                                " "
+                            },
+                            address,
+                            instruction
+                        ))
+                        .monospace();
+
+                        ui.add(
+                            egui::Label::new(match address == cpu_state.index {
+                                true => text.color(PRIMARY_COLOR),
+                                false => text,
+                            })
+                            .sense(egui::Sense::click()),
+                        )
+                        .context_menu(|ui| {
+                            if ui.button("⇥ Run to Cursor").clicked() {
+                                action = Some(DebuggerAction::RunTo(address));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+
+                    address += length;
+                }
+            });
+
+        if let Some(action) = action {
+            self.debug(ctx, action);
+        }
+    }
+
+    /// Stop capturing and save the clip
+    fn finish_capture(&mut self) {
+        let Some(capture) = self.frontend.stop_capture() else {
+            return;
+        };
+
+        let directory = self.screenshot_directory();
+        let name = self.screenshot_name();
+        let palette = self.frontend.colors.palette();
+
+        let result = fs::create_dir_all(&directory).and_then(|_| {
+            fs::write(
+                directory.join(format!("{}.gif", name)),
+                capture.gif(&palette),
+            )
+        });
+
+        let result = result.and_then(|_| {
+            if !self.state.raw_clips {
+                return Ok(());
+            }
+
+            let directory = directory.join(&name);
+            fs::create_dir_all(&directory)?;
+
+            for (i, frame) in capture.frame_sequence(&palette).enumerate() {
+                fs::write(directory.join(format!("frame-{:05}.png", i)), frame)?;
+            }
+
+            fs::write(directory.join("buzzer.wav"), capture.wav())
+        });
+
+        if let Err(error) = result {
+            eprintln!("couldn't save the clip, {}", error);
+        }
+    }
+
+    /// Stop the movie, saving it if it was being recorded
+    fn finish_movie(&mut self) {
+        let Some(movie) = self.frontend.stop_movie() else {
+            return;
+        };
+
+        if let Some(ref path) = self.state.record_movie {
+            if let Err(error) = fs::write(path, movie.to_bytes()) {
+                eprintln!("couldn't save the movie, {}", error);
+            }
+        }
+    }
+
+    /// Read a movie and switch to the variant and settings it was recorded with
+    fn load_movie(&mut self, path: &path::Path, program: &[u8]) -> Result<movie::Movie, String> {
+        let movie = movie::Movie::parse(&fs::read(path).map_err(|error| error.to_string())?)
+            .map_err(|error| error.to_string())?;
+
+        let variant = self
+            .variants
+            .iter()
+            .position(|variant| variant.id == movie.variant)
+            .ok_or_else(|| movie::MovieError::UnknownVariant(movie.variant.clone()).to_string())?;
+
+        if variant != self.state.variant {
+            self.frontend.backend = (self.variants[variant].new)();
+            self.state.variant = variant;
+        }
+
+        movie
+            .prepare(self.frontend.backend.as_mut(), program)
+            .map_err(|error| error.to_string())?;
+
+        Ok(movie)
+    }
+
+    fn load_state(&mut self, slot: usize) {
+        let Some(path) = self.state_path(slot) else {
+            return;
+        };
+
+        if self.frontend.movie().is_some() {
+            eprintln!(
+                "couldn't load the state from slot {}, a movie is playing or being recorded",
+                slot
+            );
+            return;
+        }
+
+        let result = fs::read(path)
+            .map_err(|error| error.to_string())
+            .and_then(|data| {
+                let mut persistent_storage = self.persistent_storage.borrow_mut();
+
+                self.frontend
+                    .restore_state(
+                        self.variants[self.state.variant].id,
+                        &data,
+                        persistent_storage.as_mut(),
+                    )
+                    .map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            eprintln!("couldn't load the state from slot {}, {}", slot, error);
+        }
+    }
+
+    /// Apply the profile of a program if there's one, returns true if it was applied
+    fn load_profile(&mut self, hash: &str) -> bool {
+        let text = match fs::read_to_string(self.profile_path(hash)) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return false,
+            Err(error) => {
+                eprintln!("couldn't read the profile, {}", error);
+                return false;
+            }
+        };
+
+        let profile = match config::Config::parse(&text) {
+            Ok(profile) => profile,
+            Err(error) => {
+                eprintln!("couldn't load the profile, {}", error);
+                return false;
+            }
+        };
+
+        self.apply_profile(&profile);
+        true
     }
 
     pub fn new(
         cc: &eframe::CreationContext,
-        backend: backend::Backend,
-        persistent_storage: rc::Rc<cell::RefCell<[u8; 8]>>,
+        variants: Vec<backend::Variant>,
+        settings: Settings,
+        persistent_storage: rc::Rc<cell::RefCell<[u8; backend::PERSISTENT_STORAGE_SIZE]>>,
+        data_dir: path::PathBuf,
     ) -> Self {
         let mut visuals = cc.egui_ctx.style().visuals.clone();
 
@@ -447,33 +1208,184 @@ impl App {
         visuals.window_fill = SECONDARY_COLOR;
         cc.egui_ctx.set_visuals(visuals);
 
-        let (stream, handle) = rodio::OutputStream::try_default().unwrap();
+        let config = settings.config;
+
+        let frontend = frontend::Frontend::new(settings.backend, &cc.egui_ctx);
 
-        let frontend = frontend::Frontend::new(backend, &cc.egui_ctx, handle);
         let state = State {
+            binding: None,
+            debugger: false,
+            defaults: None,
             emulation: Emulation::Stopped,
             error: Error {
                 message: String::with_capacity(128),
                 timestamp: time::Instant::now(),
             },
-            menu: Menu::BackendSelection,
-            font_path: None,
-            program_path: None,
+            menu: match settings.start || config.variant.is_some() {
+                true => Menu::Configuration,
+                false => Menu::BackendSelection,
+            },
+            font_path: config.font_path.clone(),
+            program_path: config.program_path.clone(),
             path_selection: PathSelection::Font,
+            play_movie: settings.play_movie,
+            program_hash: None,
+            raw_clips: config.raw_clips,
+            record_movie: settings.record_movie,
+            rewinding: false,
+            screenshot_directory: config.screenshot_directory.clone(),
+            screenshot_scale: config.screenshot_scale,
+            seed: None,
+            variant: settings.variant,
+            window_title: None,
         };
 
-        Self {
-            _stream: stream,
+        let mut app = Self {
+            data_dir,
             display_texture: frontend.display_texture(),
             file_picker: file_picker::FilePicker::new(),
             frontend,
             persistent_storage,
             state,
+            variants,
+        };
+
+        app.configure(&config);
+
+        if settings.start {
+            app.start();
         }
+
+        app
+    }
+
+    fn profile_path(&self, hash: &str) -> path::PathBuf {
+        self.data_dir
+            .join(PROFILES_DIRECTORY)
+            .join(format!("{}.toml", hash))
+    }
+
+    /// Report an error of the running program, returns true if it was fatal and the emulation was stopped
+    fn report_error(&mut self, ctx: &egui::Context, error: frontend::FrontendError) -> bool {
+        if !error.is_fatal() {
+            eprintln!("{}", error);
+            return false;
+        }
+
+        self.state.error.timestamp = time::Instant::now();
+        self.state.error.message.clear();
+        let _ = write!(self.state.error.message, "fatal error, {}", error);
+
+        self.finish_movie();
+        self.state.emulation = Emulation::Stopped;
+        self.state.menu = Menu::Configuration;
+        ctx.request_repaint();
+
+        true
+    }
+
+    /// Write the current settings to the config file
+    fn save_config(&mut self) {
+        let current = self.config();
+
+        let config = match self.state.defaults.clone() {
+            // the paths and the screenshot settings aren't part of profiles
+            Some(defaults) => config::Config {
+                font_path: current.font_path,
+                program_path: current.program_path,
+                raw_clips: current.raw_clips,
+                screenshot_directory: current.screenshot_directory,
+                screenshot_scale: current.screenshot_scale,
+                ..defaults
+            },
+            None => current,
+        };
+
+        if let Err(error) = fs::write(self.data_dir.join(config::FILE_NAME), config.to_string()) {
+            eprintln!("couldn't save the config, {}", error);
+        }
+    }
+
+    /// Save the current settings as the profile of the running program
+    fn save_profile(&mut self) {
+        let Some(hash) = self.state.program_hash.clone() else {
+            return;
+        };
+
+        let profile = config::Config {
+            font_path: None,
+            program_path: None,
+            screenshot_directory: None,
+            ..self.config()
+        };
+
+        if let Err(error) = fs::create_dir_all(self.data_dir.join(PROFILES_DIRECTORY))
+            .and_then(|_| fs::write(self.profile_path(&hash), profile.to_string()))
+        {
+            eprintln!("couldn't save the profile, {}", error);
+        }
+    }
+
+    /// Save the display as a PNG named after the program and the current time
+    fn save_screenshot(&mut self) {
+        let directory = self.screenshot_directory();
+        let path = directory.join(format!("{}.png", self.screenshot_name()));
+
+        let result = self
+            .frontend
+            .screenshot(self.state.screenshot_scale as usize)
+            .map_err(|error| error.to_string())
+            .and_then(|png| {
+                fs::create_dir_all(&directory)
+                    .and_then(|_| fs::write(&path, png))
+                    .map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = result {
+            eprintln!("couldn't save the screenshot, {}", error);
+        }
+    }
+
+    fn save_state(&mut self, slot: usize) {
+        let Some(path) = self.state_path(slot) else {
+            return;
+        };
+
+        let data = self.frontend.save_state(
+            self.variants[self.state.variant].id,
+            self.persistent_storage.borrow().as_ref(),
+        );
+
+        if let Err(error) = fs::create_dir_all(self.data_dir.join(STATES_DIRECTORY))
+            .and_then(|_| fs::write(path, data))
+        {
+            eprintln!("couldn't save the state to slot {}, {}", slot, error);
+        }
+    }
+
+    fn screenshot_directory(&self) -> path::PathBuf {
+        self.state
+            .screenshot_directory
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join(SCREENSHOTS_DIRECTORY))
+    }
+
+    /// Name for a screenshot or clip, the program's followed by the current time
+    fn screenshot_name(&self) -> String {
+        let program = self
+            .state
+            .program_path
+            .as_ref()
+            .and_then(|path| path.file_stem()?.to_str())
+            .unwrap_or("screenshot");
+
+        format!("{}-{}", program, timestamp())
     }
 
     pub fn start(&mut self) {
         self.state.error.message.clear();
+        self.finish_capture();
+        self.finish_movie();
 
         let font: Option<Vec<u8>> =
             match file_picker::FilePicker::load(self.state.font_path.as_ref()) {
@@ -518,9 +1430,77 @@ impl App {
             }
         };
 
+        let hash = sha1::hex_digest(&program);
+        if self.state.program_hash.as_ref() != Some(&hash) {
+            if let Some(defaults) = self.state.defaults.take() {
+                self.apply_profile(&defaults);
+            }
+            let defaults = self.config();
+
+            let entry = database::find(&hash);
+            let mut applied = true;
+
+            if let Some(entry) = entry {
+                let mut settings = config::Config {
+                    quirks: Default::default(),
+                    ..self.config()
+                };
+                entry.apply(&mut settings);
+                self.apply_profile(&settings);
+            } else if let Some(id) = backend::detect_variant(&program)
+                .filter(|_| self.variants[self.state.variant].id == "chip8")
+            {
+                let settings = config::Config {
+                    quirks: Default::default(),
+                    variant: Some(id.to_string()),
+                    ..self.config()
+                };
+                self.apply_profile(&settings);
+            } else {
+                applied = false;
+            }
+
+            applied |= self.load_profile(&hash);
+            if applied {
+                self.state.defaults = Some(defaults);
+            }
+            self.state.program_hash = Some(hash);
+            self.state.window_title = Some(match entry {
+                Some(database::Entry {
+                    authors: Some(authors),
+                    title,
+                    ..
+                }) => format!("{} by {} - Ruschip", title, authors),
+                Some(entry) => format!("{} - Ruschip", entry.title),
+                None => WINDOW_TITLE.to_string(),
+            });
+        }
+
+        let movie = match self.state.play_movie.take() {
+            Some(path) => match self.load_movie(&path, &program) {
+                Ok(movie) => Some(movie),
+                Err(error) => {
+                    self.state.error.timestamp = time::Instant::now();
+                    let _ = write!(
+                        self.state.error.message,
+                        "couldn't play the movie, {}",
+                        error
+                    );
+                    return;
+                }
+            },
+            None => None,
+        };
+
         self.frontend.reset();
+        if movie.is_none() {
+            self.frontend
+                .backend
+                .set_seed(self.state.seed.unwrap_or_else(backend::random::new_seed));
+        }
 
         if let Err(error) = self.frontend.backend.load(font.as_deref(), &program) {
+            self.state.program_hash = None;
             self.state.program_path = None;
             self.state.error.timestamp = time::Instant::now();
             let _ = write!(
@@ -531,24 +1511,35 @@ impl App {
             return;
         };
 
+        match movie {
+            Some(movie) => self.frontend.play_movie(movie),
+            None if self.state.record_movie.is_some() => {
+                let movie = movie::Movie::new(
+                    self.frontend.backend.as_mut(),
+                    self.variants[self.state.variant].id,
+                    &program,
+                    self.frontend.instructions_per_tick,
+                    self.persistent_storage.borrow().as_ref(),
+                );
+                self.frontend.record_movie(movie);
+            }
+            None => (),
+        }
+
+        self.save_config();
+
         self.state.emulation = Emulation::Running;
         self.state.menu = Menu::Inactive;
     }
-}
 
-impl BackendSelection {
-    pub fn get(backend: &backend::Backend) -> Self {
-        match backend {
-            backend::Backend::Chip8(..) => Self::Chip8,
-            backend::Backend::SuperChip(..) => Self::SuperChip,
-        }
-    }
+    fn state_path(&self, slot: usize) -> Option<path::PathBuf> {
+        let program = self.state.program_path.as_ref()?.file_stem()?.to_str()?;
 
-    pub fn into_backend(&self) -> backend::Backend {
-        match self {
-            Self::Chip8 => backend::Backend::Chip8(Default::default()),
-            Self::SuperChip => backend::Backend::SuperChip(Default::default()),
-        }
+        Some(
+            self.data_dir
+                .join(STATES_DIRECTORY)
+                .join(format!("{}.{}.state", program, slot)),
+        )
     }
 }
 
@@ -557,6 +1548,8 @@ impl ColorSelection {
         match self {
             Self::Active => &mut colors.active,
             Self::Inactive => &mut colors.inactive,
+            Self::Overlap => &mut colors.overlap,
+            Self::Secondary => &mut colors.secondary,
         }
     }
 }
@@ -574,7 +1567,9 @@ impl QuirkSelection {
     pub fn get_quirk_mut<'a>(&self, options: &'a mut backend::Options) -> &'a mut bool {
         match self {
             Self::CopyAndShift => &mut options.copy_and_shift,
+            Self::DisplayWait => &mut options.display_wait,
             Self::IncrementAddress => &mut options.increment_address,
+            Self::MachineCode => &mut options.machine_code,
             Self::QuirkyJump => &mut options.quirky_jump,
             Self::ResetFlag => &mut options.reset_flag,
         }
@@ -593,3 +1588,34 @@ pub fn menu_item(
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), add_contents);
     });
 }
+
+/// The current UTC time as `YYYY-MM-DD_HH-MM-SS-mmm`, which sorts chronologically
+fn timestamp() -> String {
+    let duration = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = duration.as_secs();
+
+    // the civil calendar from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        duration.subsec_millis()
+    )
+}
@@ -0,0 +1,73 @@
+//    Copyright (C) 2023 Segmentation Violator <segmentationviolator@proton.me>
+
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Encode 16-bit mono samples as a WAV file
+pub fn encode(sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+    let data_size = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    // PCM with a single channel of 16 bits
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_and_samples() {
+        let wav = encode(44100, &[0, 1, -1, i16::MAX, i16::MIN]);
+
+        let mut expected = b"RIFF".to_vec();
+        expected.extend_from_slice(&[46, 0, 0, 0]);
+        expected.extend_from_slice(b"WAVEfmt ");
+        expected.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+        // 44100 samples and 88200 bytes per second, 2 bytes per sample of 16 bits
+        expected.extend_from_slice(&[0x44, 0xAC, 0, 0, 0x88, 0x58, 0x01, 0, 2, 0, 16, 0]);
+        expected.extend_from_slice(b"data");
+        expected.extend_from_slice(&[10, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 0, 1, 0, 0xFF, 0xFF, 0xFF, 0x7F, 0x00, 0x80]);
+
+        assert_eq!(wav, expected);
+    }
+
+    #[test]
+    fn no_samples() {
+        let wav = encode(8000, &[]);
+
+        assert_eq!(wav.len(), 44);
+        assert_eq!(wav[4..8], 36u32.to_le_bytes());
+        assert_eq!(wav[40..], [0, 0, 0, 0]);
+    }
+}